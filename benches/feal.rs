@@ -0,0 +1,125 @@
+// vim: expandtab shiftwidth=4 tabstop=4:
+
+/* The numbers to look at before and after touching feal.rs:
+ *     cargo bench --bench feal
+ *     cargo bench --bench feal -- backends      (just the one group)
+ * criterion keeps the last run in target/criterion and says what changed.
+ *
+ * backends is the one that compares the scalar, bitsliced and SIMD ways of
+ * encrypting a pile of blocks; its throughput is in blocks per second.
+ * buffer is the byte-oriented side, in bytes per second: encrypt_block
+ * block by block over a buffer, and the CBC that mac.rs has (there are no
+ * other modes of operation to time).
+ * attacks times attack.rs end to end; build with --features parallel to
+ * see what the threads buy.
+ */
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use feal4::{attack, bitslice, feal, mac, simd};
+use feal4::feal::Feal;
+use feal4::simd::Backend;
+
+const KEY: u64 = 0x0123456789abcdef;
+const BLOCKS: usize = 4096;
+
+fn primitives(c: &mut Criterion) {
+    let mut group = c.benchmark_group("primitives");
+    group.bench_function("gx", |b| b.iter(|| feal::gx(black_box(1), black_box(0x5a), black_box(0xc3))));
+    group.bench_function("f", |b| b.iter(|| feal::f(black_box(0x015f), black_box(0xe529577a))));
+    group.bench_function("fk32", |b| b.iter(|| feal::fk32(black_box(0x01234567), black_box(0x89abcdef))));
+    group.bench_function("keygen", |b| b.iter(|| feal::keygen(black_box(0x01234567), black_box(0x89abcdef))));
+    group.bench_function("keygen_rounds 32", |b| b.iter(|| feal::keygen_rounds(black_box(0x01234567), black_box(0x89abcdef), 32)));
+    group.finish();
+}
+
+// One block at a time, with the key schedule worked out every time (encrypt/decrypt)
+// or once up front (Feal).
+fn block(c: &mut Criterion) {
+    let mut group = c.benchmark_group("block");
+    let schedule = Feal::new(KEY, 8);
+    group.bench_function("encrypt", |b| b.iter(|| feal::encrypt(black_box(KEY), black_box(0))));
+    group.bench_function("decrypt", |b| b.iter(|| feal::decrypt(black_box(KEY), black_box(0xceef2c86f2490752))));
+    group.bench_function("Feal::encrypt", |b| b.iter(|| schedule.encrypt(black_box(0))));
+    group.bench_function("Feal::decrypt", |b| b.iter(|| schedule.decrypt(black_box(0xceef2c86f2490752))));
+    group.finish();
+}
+
+// BLOCKS random blocks under one key, every way we have of doing that.
+fn backends(c: &mut Criterion) {
+    let mut group = c.benchmark_group("backends");
+    group.throughput(Throughput::Elements(BLOCKS as u64));
+    let mut rng = StdRng::seed_from_u64(0);
+    let blocks: Vec<u64> = (0..BLOCKS).map(|_| rng.gen()).collect();
+
+    for rounds in [4, 8, 32] {
+        let schedule = Feal::new(KEY, rounds);
+        group.bench_with_input(BenchmarkId::new("fealn_raw", rounds), &blocks, |b, blocks| {
+            let mut scratch = blocks.clone();
+            b.iter(|| {
+                for block in scratch.iter_mut() {
+                    *block = feal::fealn_raw(&schedule.encryption, rounds, *block);
+                }
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("bitslice u64", rounds), &blocks, |b, blocks| {
+            let mut scratch = blocks.clone();
+            b.iter(|| bitslice::encrypt::<u64>(&schedule.encryption, rounds, &mut scratch))
+        });
+        group.bench_with_input(BenchmarkId::new("bitslice u128", rounds), &blocks, |b, blocks| {
+            let mut scratch = blocks.clone();
+            b.iter(|| bitslice::encrypt::<u128>(&schedule.encryption, rounds, &mut scratch))
+        });
+        for backend in Backend::available() {
+            group.bench_with_input(BenchmarkId::new(format!("simd {:?}", backend), rounds), &blocks, |b, blocks| {
+                let mut scratch = blocks.clone();
+                b.iter(|| simd::encrypt_blocks_with(backend, &schedule, &mut scratch))
+            });
+        }
+    }
+    group.finish();
+}
+
+// BLOCKS blocks' worth of bytes under one key.
+fn buffer(c: &mut Criterion) {
+    let mut group = c.benchmark_group("buffer");
+    group.throughput(Throughput::Bytes((BLOCKS * mac::BLOCK) as u64));
+    let mut rng = StdRng::seed_from_u64(0);
+    let bytes: Vec<u8> = (0..BLOCKS * mac::BLOCK).map(|_| rng.gen()).collect();
+    let schedule = Feal::new(KEY, 8);
+
+    group.bench_function("encrypt_block", |b| {
+        let mut scratch = bytes.clone();
+        b.iter(|| {
+            for block in scratch.chunks_exact_mut(mac::BLOCK) {
+                schedule.encrypt_block(block.try_into().unwrap());
+            }
+            black_box(&scratch);
+        })
+    });
+    group.bench_function("cbc_encrypt", |b| b.iter(|| mac::cbc_encrypt(&schedule, [0; mac::BLOCK], black_box(&bytes))));
+    group.bench_function("cmac", |b| b.iter(|| mac::cmac(&schedule, black_box(&bytes))));
+    group.finish();
+}
+
+fn attacks(c: &mut Criterion) {
+    let mut group = c.benchmark_group("attacks");
+    group.sample_size(10);
+    let schedule = Feal::new(KEY, attack::ROUNDS);
+    group.bench_function("chosen_pairs 65536", |b| b.iter(|| attack::chosen_pairs(&schedule, 1 << 16, black_box(0))));
+    let pairs = attack::chosen_pairs(&schedule, 8, 0);
+    group.bench_function("last_round_keys", |b| b.iter(|| {
+        let pairs = black_box(&pairs);
+        attack::last_round_keys(pairs, &attack::inner_survivors(pairs))
+    }));
+    let known = [(0, Feal::new(KEY, 8).encrypt(0))];
+    group.bench_function("brute_force 2^16 keys", |b| b.iter(|| attack::brute_force(black_box(&known), 8, KEY..KEY + (1 << 16))));
+    group.finish();
+}
+
+criterion_group!(benches, primitives, block, backends, buffer, attacks);
+criterion_main!(benches);
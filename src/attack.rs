@@ -0,0 +1,162 @@
+// vim: expandtab shiftwidth=4 tabstop=4:
+
+/* The FEAL-4 last round attack (candidates.rs has the long version of how
+ * it works), for running from the command line on as many pairs and cores
+ * as you like, plus a brute force over a range of master keys.
+ *
+ * With the "parallel" feature the pair generation, the candidate scoring
+ * and the brute force are spread over threads with rayon. The answers
+ * don't change: every chunk of pairs gets its own RNG seeded from the
+ * seed and the chunk's number, and results come back in candidate order,
+ * however many threads there are (RAYON_NUM_THREADS picks that).
+ */
+
+use std::ops::Range;
+
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use crate::feal::Feal;
+
+pub mod candidates;
+
+pub use candidates::{effective_key, Pair, Stage, MIN_PAIRS, PLAINTEXT_DIFFERENTIAL, ROUNDS};
+use candidates::{last_round, CANDIDATES};
+
+// Pairs per RNG.
+const PAIRS_PER_CHUNK: u64 = 1024;
+// Master keys per brute force job.
+const KEYS_PER_CHUNK: u64 = 1 << 16;
+
+// f(index) for every index, in order, on as many threads as rayon has if we're parallel.
+fn map_range<T: Send>(range: Range<u64>, f: impl Fn(u64) -> T + Sync + Send) -> Vec<T> {
+    #[cfg(feature = "parallel")]
+    {
+        range.into_par_iter().map(f).collect()
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        range.map(f).collect()
+    }
+}
+
+// One SplitMix64 step, to turn (seed, chunk) into an RNG seed: seed + chunk would
+// give seed 1's chunk 0 the same plaintexts as seed 0's chunk 1.
+fn splitmix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+// count chosen plaintext pairs, encrypted under feal (which the attacker doesn't get to see).
+pub fn chosen_pairs(feal: &Feal, count: usize, seed: u64) -> Vec<Pair> {
+    let count = count as u64;
+    let chunks = count.div_ceil(PAIRS_PER_CHUNK);
+    map_range(0..chunks, |chunk| {
+        let mut rng = StdRng::seed_from_u64(splitmix(splitmix(seed) ^ chunk));
+        let size = PAIRS_PER_CHUNK.min(count - chunk * PAIRS_PER_CHUNK);
+        (0..size).map(|_| {
+            let p1: u64 = rng.gen();
+            Pair {c1: feal.encrypt(p1), c2: feal.encrypt(p1 ^ PLAINTEXT_DIFFERENTIAL)}
+        }).collect::<Vec<Pair>>()
+    }).concat()
+}
+
+// For each of the stage's 2^16 candidates, how many pairs in a row it gets right.
+pub fn score(stage: Stage, pairs: &[Pair]) -> Vec<u32> {
+    let pairs: Vec<(u32, u32, u32)> = pairs.iter().map(last_round).collect();
+    map_range(0..CANDIDATES as u64, |candidate| stage.passed(candidate as u32, &pairs) as u32)
+}
+
+// The candidates that got every pair right.
+pub fn survivors(scores: &[u32], pairs: usize) -> Vec<u32> {
+    scores.iter().enumerate().filter(|(_, score)| **score as usize == pairs).map(|(candidate, _)| candidate as u32).collect()
+}
+
+// The K0 ^ K1, K2 ^ K3 candidates that fit all the pairs. Nothing if there are
+// fewer than MIN_PAIRS of them, rather than billions of keys later on.
+pub fn inner_survivors(pairs: &[Pair]) -> Vec<u32> {
+    if pairs.len() < MIN_PAIRS {
+        return vec![];
+    }
+    survivors(&score(Stage::Inner, pairs), pairs.len())
+}
+
+// Every K that fits all the pairs, smallest first, given the inner_survivors.
+pub fn last_round_keys(pairs: &[Pair], inner: &[u32]) -> Vec<u32> {
+    let mut keys: Vec<u32> = inner.iter().flat_map(|inner| {
+        let stage = Stage::Outer {inner: *inner as u16};
+        survivors(&score(stage, pairs), pairs.len()).into_iter().map(move |candidate| stage.key(candidate))
+    }).collect();
+    keys.sort();
+    keys
+}
+
+// Every master key in keys that turns each known plaintext into its ciphertext under FEAL-<rounds>.
+pub fn brute_force(known: &[(u64, u64)], rounds: usize, keys: Range<u64>) -> Vec<u64> {
+    let chunks = (keys.end - keys.start).div_ceil(KEYS_PER_CHUNK);
+    map_range(0..chunks, |chunk| {
+        let start = keys.start + chunk * KEYS_PER_CHUNK;
+        let end = keys.end.min(start + KEYS_PER_CHUNK);
+        (start..end).filter(|key| {
+            let feal = Feal::new(*key, rounds);
+            known.iter().all(|(plaintext, ciphertext)| feal.encrypt(*plaintext) == *ciphertext)
+        }).collect::<Vec<u64>>()
+    }).concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_last_round_key() {
+        for seed in 0..3 {
+            let feal = Feal::new(0x0123456789abcdef ^ seed, ROUNDS);
+            let pairs = chosen_pairs(&feal, 6, seed);
+            assert!(last_round_keys(&pairs, &inner_survivors(&pairs)).contains(&effective_key(&feal.encryption)));
+        }
+    }
+
+    #[test]
+    fn too_few_pairs() {
+        let feal = Feal::new(0x0123456789abcdef, ROUNDS);
+        assert!(inner_survivors(&chosen_pairs(&feal, MIN_PAIRS - 1, 0)).is_empty());
+        assert!(inner_survivors(&[]).is_empty());
+    }
+
+    #[test]
+    fn seeds_dont_overlap() {
+        // Seed 0's second chunk isn't seed 1's first.
+        let feal = Feal::new(0x0123456789abcdef, ROUNDS);
+        let chunk = PAIRS_PER_CHUNK as usize;
+        assert_ne!(chosen_pairs(&feal, 2 * chunk, 0)[chunk..], chosen_pairs(&feal, chunk, 1)[..]);
+    }
+
+    #[test]
+    fn brute_force_finds_the_key() {
+        let key = 0x0123456789ab0123;
+        let feal = Feal::new(key, 8);
+        let known: Vec<(u64, u64)> = [0, 1].iter().map(|plaintext| (*plaintext, feal.encrypt(*plaintext))).collect();
+        assert_eq!(brute_force(&known, 8, key - 100_000..key + 100_000), vec![key]);
+    }
+
+    // The same answers on one thread as on four.
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn deterministic_across_thread_counts() {
+        let feal = Feal::new(0x0123456789abcdef, ROUNDS);
+        let run = |threads: usize| {
+            let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+            pool.install(|| {
+                let pairs = chosen_pairs(&feal, 5000, 7);
+                let inner = inner_survivors(&pairs[..8]);
+                (pairs.clone(), score(Stage::Inner, &pairs[..8]), last_round_keys(&pairs[..8], &inner))
+            })
+        };
+        assert_eq!(run(1), run(4));
+    }
+}
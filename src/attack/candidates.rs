@@ -0,0 +1,101 @@
+// vim: expandtab shiftwidth=4 tabstop=4:
+
+/* The heart of the classic last round attack on FEAL-4: what a candidate key
+ * is and whether it fits a pair. The command line's attack.rs runs it with
+ * rayon, and the webapp includes this very file (with #[path]) for its web
+ * workers, so keep it free of rayon and yew.
+ *
+ * With a plaintext differential of 0x8080000080800000 the input whitening and
+ * right ^= left leave (0x80800000, 0) going into round 0, so
+ *     round 0: F sees 0,          (L, R) = (0, 0x80800000)
+ *     round 1: F sees 0x80800000, which always comes out as 0x02000000,
+ *              (L, R) = (0x80800000, 0x02000000)
+ *     round 2: F sees 0x02000000, (L, R) = (0x02000000, whatever)
+ *     round 3: R4 = 0x02000000 ^ F(k3, R3), L4 = R3
+ * and the ciphertext is (R4, L4 ^ R4) ^ the output whitening. So for the right
+ * key, the F of the last round has to turn the R3 pair into a difference of
+ * (difference of the ciphertext's left half) ^ 0x02000000, for every pair.
+ *
+ * We can't see R3 itself, only R3 ^ (the two halves of the output whitening
+ * xored together), and f(k3, a) is f(0, a ^ (0, k3_0, k3_1, 0)), so what we
+ * find is the 32 bit K = k3 and that whitening rolled into one: F(k3, R3) is
+ * f(0, cl ^ ch ^ K). That's all the last round needs to be peeled off.
+ *
+ * Rather than try all 2^32 K's, the middle two bytes of f only depend on
+ * K0 ^ K1 and K2 ^ K3 (the inner stage, 2^16 guesses), and then for each of
+ * those that survives, K0 and K2 (the outer stage, 2^16 more) fix the rest.
+ */
+
+use serde::{Deserialize, Serialize};
+
+use crate::feal::{f, u16tou8, u64tou32, u8tou32};
+
+pub const ROUNDS: usize = 4;
+pub const PLAINTEXT_DIFFERENTIAL: u64 = 0x8080000080800000;
+// What comes out of round 1's F, and so the left half going into round 3.
+const ROUND2_LEFT: u32 = 0x02000000;
+// Candidates per stage.
+pub const CANDIDATES: u32 = 1 << 16;
+// With fewer pairs than this, too many candidates fit them all: with none,
+// every one of the 2^16 inner candidates does, and then so do all 2^16 outer
+// ones for each, which is 2^32 keys.
+pub const MIN_PAIRS: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Pair {
+    pub c1: u64,
+    pub c2: u64,
+}
+
+// The K the attack should find for these subkeys, to check the answer against:
+// k3 with the two halves of the output whitening rolled in.
+pub fn effective_key(subkeys: &[u16]) -> u32 {
+    let whitening = ((subkeys[ROUNDS + 4] as u32) << 16 | subkeys[ROUNDS + 5] as u32) ^ ((subkeys[ROUNDS + 6] as u32) << 16 | subkeys[ROUNDS + 7] as u32);
+    let (k0, k1) = u16tou8(subkeys[ROUNDS - 1]);
+    whitening ^ u8tou32(0, k0, k1, 0)
+}
+
+// The last round's R3 (up to K) for both ciphertexts, and the difference F has to make.
+pub fn last_round(pair: &Pair) -> (u32, u32, u32) {
+    let (ch1, cl1) = u64tou32(pair.c1);
+    let (ch2, cl2) = u64tou32(pair.c2);
+    (ch1 ^ cl1, ch2 ^ cl2, ch1 ^ ch2 ^ ROUND2_LEFT)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Stage {
+    // Candidates are (K0 ^ K1) << 8 | (K2 ^ K3), and only the middle two bytes of f can be checked.
+    Inner,
+    // Candidates are K0 << 8 | K2, for this inner candidate.
+    Outer {inner: u16},
+}
+
+impl Stage {
+    pub fn key(&self, candidate: u32) -> u32 {
+        match self {
+            Stage::Inner => {
+                let (k01, k23) = ((candidate >> 8) as u8, candidate as u8);
+                u8tou32(0, k01, k23, 0)
+            },
+            Stage::Outer {inner} => {
+                let (k01, k23) = ((inner >> 8) as u8, *inner as u8);
+                let (k0, k2) = ((candidate >> 8) as u8, candidate as u8);
+                u8tou32(k0, k0 ^ k01, k2, k2 ^ k23)
+            },
+        }
+    }
+
+    fn mask(&self) -> u32 {
+        match self {
+            Stage::Inner => 0x00ffff00,
+            Stage::Outer {..} => 0xffffffff,
+        }
+    }
+
+    // How many of the pairs (as last_round has them) the candidate gets right
+    // before the first one it gets wrong, so all of them if it survives.
+    pub fn passed(&self, candidate: u32, pairs: &[(u32, u32, u32)]) -> usize {
+        let (key, mask) = (self.key(candidate), self.mask());
+        pairs.iter().take_while(|(a1, a2, expected)| (f(0, a1 ^ key) ^ f(0, a2 ^ key)) & mask == expected & mask).count()
+    }
+}
@@ -0,0 +1,294 @@
+// vim: expandtab shiftwidth=4 tabstop=4:
+
+/* Bitsliced FEAL-N: one machine word per bit position, one bit of that
+ * word per block, so every bit operation below works on a whole batch of
+ * blocks at once (64 with u64 words, 128 with u128).
+ *
+ * A byte is [W; 8] with bit i (bit 0 is the least significant) in word i,
+ * a 32 bit half is [W; 32] and a block is [W; 64], the same way. The only
+ * thing in FEAL that isn't an xor is the add in gx, and that's a ripple
+ * carry adder here. The rotate by 2 after it is just picking which word
+ * goes where, so it's free.
+ *
+ * encrypt() does the transposing in and out for you; fealn() is the
+ * cipher itself, for when the blocks are bitsliced already. The subkeys
+ * are bitsliced too, so every block can have its own key if you like,
+ * but broadcast() makes the usual all-the-same ones.
+ */
+
+use std::ops::{BitAnd, BitOr, BitXor};
+
+// A word with a bit per block.
+pub trait Lanes: Copy + BitAnd<Output = Self> + BitOr<Output = Self> + BitXor<Output = Self> {
+    const LANES: usize;
+    const ZERO: Self;
+    const ONES: Self;
+
+    // The lanes in 64 bit pieces, for transposing.
+    fn chunk(&self, idx: usize) -> u64;
+    fn set_chunk(&mut self, idx: usize, bits: u64);
+}
+
+impl Lanes for u64 {
+    const LANES: usize = 64;
+    const ZERO: u64 = 0;
+    const ONES: u64 = u64::MAX;
+
+    fn chunk(&self, _idx: usize) -> u64 {
+        *self
+    }
+
+    fn set_chunk(&mut self, _idx: usize, bits: u64) {
+        *self = bits;
+    }
+}
+
+impl Lanes for u128 {
+    const LANES: usize = 128;
+    const ZERO: u128 = 0;
+    const ONES: u128 = u128::MAX;
+
+    fn chunk(&self, idx: usize) -> u64 {
+        (*self >> (64 * idx)) as u64
+    }
+
+    fn set_chunk(&mut self, idx: usize, bits: u64) {
+        *self = (*self & !((u64::MAX as u128) << (64 * idx))) | ((bits as u128) << (64 * idx));
+    }
+}
+
+// Transpose a 64x64 bit matrix in place (Hacker's Delight 7-3, widened to 64 bits).
+// Bit 63 - j of row i swaps with bit 63 - i of row j, and doing it twice gets you back.
+fn transpose64(rows: &mut [u64; 64]) {
+    let mut width = 32;
+    let mut mask: u64 = 0x00000000ffffffff;
+    while width != 0 {
+        let mut idx = 0;
+        while idx < 64 {
+            let swap = (rows[idx] ^ (rows[idx + width] >> width)) & mask;
+            rows[idx] ^= swap;
+            rows[idx + width] ^= swap << width;
+            idx = (idx + width + 1) & !width;
+        }
+        width >>= 1;
+        mask ^= mask << width;
+    }
+}
+
+// Up to W::LANES blocks into bit slices (missing blocks are 0).
+pub fn to_slices<W: Lanes>(blocks: &[u64]) -> [W; 64] {
+    assert!(blocks.len() <= W::LANES, "{} blocks don't fit in {} lanes", blocks.len(), W::LANES);
+    let mut slices = [W::ZERO; 64];
+    for (idx, chunk) in blocks.chunks(64).enumerate() {
+        let mut rows = [0u64; 64];
+        rows[..chunk.len()].copy_from_slice(chunk);
+        transpose64(&mut rows);
+        for (bit, slice) in slices.iter_mut().enumerate() {
+            slice.set_chunk(idx, rows[63 - bit]);
+        }
+    }
+    slices
+}
+
+// And back again, into however many blocks there are (at most W::LANES).
+pub fn from_slices<W: Lanes>(slices: &[W; 64], blocks: &mut [u64]) {
+    assert!(blocks.len() <= W::LANES, "{} blocks don't fit in {} lanes", blocks.len(), W::LANES);
+    for (idx, chunk) in blocks.chunks_mut(64).enumerate() {
+        let mut rows = [0u64; 64];
+        for (bit, slice) in slices.iter().enumerate() {
+            rows[63 - bit] = slice.chunk(idx);
+        }
+        transpose64(&mut rows);
+        let count = chunk.len();
+        chunk.copy_from_slice(&rows[..count]);
+    }
+}
+
+// The same value in every lane.
+fn splat<W: Lanes, const BITS: usize>(value: u64) -> [W; BITS] {
+    let mut slices = [W::ZERO; BITS];
+    for (bit, slice) in slices.iter_mut().enumerate() {
+        if (value >> bit) & 1 == 1 {
+            *slice = W::ONES;
+        }
+    }
+    slices
+}
+
+// The subkeys (laid out the way keygen_rounds does it) for every lane.
+pub fn broadcast<W: Lanes>(k: &[u16]) -> Vec<[W; 16]> {
+    k.iter().map(|subkey| splat(*subkey as u64)).collect()
+}
+
+fn xor<W: Lanes, const BITS: usize>(a: &[W; BITS], b: &[W; BITS]) -> [W; BITS] {
+    let mut out = *a;
+    for (out, b) in out.iter_mut().zip(b.iter()) {
+        *out = *out ^ *b;
+    }
+    out
+}
+
+// Bits [8 * idx, 8 * idx + 8) of a bitsliced value.
+fn byte<W: Lanes, const BITS: usize>(value: &[W; BITS], idx: usize) -> [W; 8] {
+    let mut out = [W::ZERO; 8];
+    out.copy_from_slice(&value[8 * idx..8 * idx + 8]);
+    out
+}
+
+// rotate left two bits((a + b + x) mod 256), like feal::gx.
+pub fn gx<W: Lanes>(x: u8, a: &[W; 8], b: &[W; 8]) -> [W; 8] {
+    let mut carry = if x & 1 == 1 { W::ONES } else { W::ZERO };
+    let mut out = [W::ZERO; 8];
+    for bit in 0..8 {
+        let partial = a[bit] ^ b[bit];
+        out[(bit + 2) % 8] = partial ^ carry;
+        carry = (a[bit] & b[bit]) | (carry & partial);
+    }
+    out
+}
+
+// feal::f (and fyoutube): a0 is the top byte of a, b0 the top byte of the subkey.
+pub fn f<W: Lanes>(k: &[W; 16], a: &[W; 32]) -> [W; 32] {
+    let (a0, a1, a2, a3) = (byte(a, 3), byte(a, 2), byte(a, 1), byte(a, 0));
+    let (b0, b1) = (byte(k, 1), byte(k, 0));
+    let v1 = xor(&a0, &xor(&b0, &a1));
+    let v2 = xor(&xor(&b1, &a2), &a3);
+    let v3 = gx(1, &v1, &v2);
+    let v4 = gx(0, &v2, &v3);
+    let v5 = gx(0, &a0, &v3);
+    let v6 = gx(1, &a3, &v4);
+
+    let mut out = [W::ZERO; 32];
+    out[0..8].copy_from_slice(&v6);
+    out[8..16].copy_from_slice(&v4);
+    out[16..24].copy_from_slice(&v3);
+    out[24..32].copy_from_slice(&v5);
+    out
+}
+
+// A 64 bit whitening key out of four subkeys, like feal::u16tou64.
+fn whitening<W: Lanes>(k: &[[W; 16]]) -> [W; 64] {
+    let mut out = [W::ZERO; 64];
+    for (idx, subkey) in k.iter().take(4).enumerate() {
+        out[48 - 16 * idx..64 - 16 * idx].copy_from_slice(subkey);
+    }
+    out
+}
+
+// feal::fealn_raw on bitsliced blocks.
+pub fn fealn<W: Lanes>(k: &[[W; 16]], rounds: usize, input: &[W; 64]) -> [W; 64] {
+    let v1 = xor(input, &whitening(&k[rounds..rounds + 4]));
+    let mut left = [W::ZERO; 32];
+    let mut right = [W::ZERO; 32];
+    left.copy_from_slice(&v1[32..64]);
+    right.copy_from_slice(&v1[0..32]);
+
+    right = xor(&right, &left);
+
+    for subkey in k.iter().take(rounds) {
+        (left, right) = (right, xor(&left, &f(subkey, &right)));
+    }
+    left = xor(&left, &right);
+
+    let mut combined = [W::ZERO; 64];
+    combined[0..32].copy_from_slice(&left);
+    combined[32..64].copy_from_slice(&right);
+    xor(&combined, &whitening(&k[rounds + 4..rounds + 8]))
+}
+
+// Encrypt blocks in place, W::LANES at a time, with the subkeys from keygen_rounds.
+// Gives the same answers as feal::fealn_raw on each block.
+pub fn encrypt<W: Lanes>(k: &[u16], rounds: usize, blocks: &mut [u64]) {
+    let subkeys = broadcast::<W>(k);
+    for chunk in blocks.chunks_mut(W::LANES) {
+        let output = fealn(&subkeys, rounds, &to_slices(chunk));
+        from_slices(&output, chunk);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+
+    use super::*;
+    use crate::feal;
+
+    #[test]
+    fn slices_round_trip() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let blocks: Vec<u64> = (0..100).map(|_| rng.gen()).collect();
+        let mut back = vec![0; 100];
+        from_slices(&to_slices::<u128>(&blocks), &mut back);
+        assert_eq!(blocks, back);
+    }
+
+    #[test]
+    fn gx_matches_scalar() {
+        for x in 0..2 {
+            for a in 0..=255u8 {
+                // One lane per b.
+                let blocks: Vec<u64> = (0..=255u64).map(|b| (a as u64) << 8 | b).collect();
+                for chunk in blocks.chunks(64) {
+                    let slices = to_slices::<u64>(chunk);
+                    let out: [u64; 8] = gx(x, &byte(&slices, 1), &byte(&slices, 0));
+                    let mut padded = [0u64; 64];
+                    padded[..8].copy_from_slice(&out);
+                    let mut results = vec![0; chunk.len()];
+                    from_slices(&padded, &mut results);
+                    for (block, result) in chunk.iter().zip(results.iter()) {
+                        assert_eq!(*result as u8, feal::gx(x, a, *block as u8), "gx({}, {:02x}, {:02x})", x, a, *block as u8);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn f_matches_scalar() {
+        let mut rng = StdRng::seed_from_u64(2);
+        for _ in 0..16 {
+            let subkey: u16 = rng.gen();
+            let inputs: Vec<u64> = (0..64).map(|_| rng.gen::<u32>() as u64).collect();
+            let slices = to_slices::<u64>(&inputs);
+            let mut half = [0u64; 32];
+            half.copy_from_slice(&slices[..32]);
+            let out = f(&splat(subkey as u64), &half);
+            let mut padded = [0u64; 64];
+            padded[..32].copy_from_slice(&out);
+            let mut results = vec![0; 64];
+            from_slices(&padded, &mut results);
+            for (input, result) in inputs.iter().zip(results.iter()) {
+                assert_eq!(*result as u32, feal::f(subkey, *input as u32));
+            }
+        }
+    }
+
+    #[test]
+    fn feal8_test_vector() {
+        let (ka, kb) = feal::u64tou32(0x0123456789abcdef);
+        let k = feal::keygen(ka, kb);
+        let mut blocks = [0u64; 3];
+        encrypt::<u64>(&k, 8, &mut blocks);
+        assert_eq!(blocks, [0xceef2c86f2490752; 3]);
+    }
+
+    #[test]
+    fn encrypt_matches_scalar() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for rounds in [4, 8, 16, 32] {
+            let k = feal::keygen_rounds(rng.gen(), rng.gen(), rounds);
+            // Not a multiple of the lanes, so the last batch is a partial one.
+            let blocks: Vec<u64> = (0..300).map(|_| rng.gen()).collect();
+            let expected: Vec<u64> = blocks.iter().map(|block| feal::fealn_raw(&k, rounds, *block)).collect();
+
+            let mut narrow = blocks.clone();
+            encrypt::<u64>(&k, rounds, &mut narrow);
+            assert_eq!(narrow, expected, "FEAL-{} with u64", rounds);
+
+            let mut wide = blocks.clone();
+            encrypt::<u128>(&k, rounds, &mut wide);
+            assert_eq!(wide, expected, "FEAL-{} with u128", rounds);
+        }
+    }
+}
@@ -0,0 +1,335 @@
+// vim: expandtab shiftwidth=4 tabstop=4:
+
+/* Our trace next to the worked example in misc/call-5-utf8.txt (section
+ * 6: FEAL-NX, N = 32, K = 0123456789ABCDEF0123456789ABCDEF, P = 0), one
+ * published value per line, with the first one we get wrong flagged:
+ *     cargo run -- explain
+ * The numbers are read out of the document itself rather than copied in
+ * here, so it's the NTT's figures we're checking against, not mine.
+ * The document is mostly prose around the numbers, so parse() goes
+ * looking for the phrase before each one and takes the hex bytes up to
+ * the next （１６進数）.
+ *
+ * Everything matches bar one: the table's f(R25, K25) is misprinted as
+ * D7 0D 85 FA, where its own R26 only works out with B7 0D 85 FA. That's
+ * in KNOWN_MISPRINTS, so it gets labelled as such rather than flagged.
+ */
+
+use crate::feal::Feal;
+use crate::trace::{bytes, encrypt_traced, Trace};
+
+pub const CALL5: &str = include_str!("../misc/call-5-utf8.txt");
+
+// (step, what call-5 prints, what it should have printed). R26 = L25 ^ f =
+// C6 58 51 F1 ^ B7 0D 85 FA = 71 55 D4 0B, as the table itself says.
+pub const KNOWN_MISPRINTS: &[(&str, &str, &str)] = &[("f(R25, K25)", "D7 0D 85 FA", "B7 0D 85 FA")];
+
+// One line of the 処理ステージ table: round 0 has no subkey or f.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Row {
+    pub round: usize,
+    pub left: u32,
+    pub right: u32,
+    pub subkey: Option<u16>,
+    pub f_output: Option<u32>,
+}
+
+// Everything section 6 writes down.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Published {
+    pub key: u128,
+    pub plaintext: u64,
+    pub subkeys: Vec<u16>,
+    // (L0, R0) ^ (K32, K33, K34, K35)
+    pub whitened: u64,
+    // ^ (φ, L0)
+    pub mixed: u64,
+    pub rows: Vec<Row>,
+    // (R32, L32)
+    pub swapped: u64,
+    // ^ (φ, R32)
+    pub unmixed: u64,
+    pub ciphertext: u64,
+}
+
+impl Published {
+    pub fn rounds(&self) -> usize {
+        self.rows.len() - 1
+    }
+}
+
+// The document writes its digits full width more often than not.
+fn ascii(text: &str) -> String {
+    text.chars().map(|c| match c {
+        '０'..='９' => char::from(b'0' + (c as u32 - '０' as u32) as u8),
+        _ => c,
+    }).collect()
+}
+
+// "19 6A 9A B1" (ignoring anything that isn't a two digit hex byte, like the bit series).
+fn hex_bytes(line: &str) -> Vec<u8> {
+    line.split_whitespace().filter(|token| token.len() == 2).filter_map(|token| u8::from_str_radix(token, 16).ok()).collect()
+}
+
+fn value(bytes: &[u8]) -> u128 {
+    bytes.iter().fold(0, |acc, byte| acc << 8 | *byte as u128)
+}
+
+// The bytes from the line containing marker up to the next （１６進数）.
+fn hex_after(lines: &[&str], marker: &str, count: usize) -> Result<u128, String> {
+    let start = lines.iter().position(|line| line.contains(marker)).ok_or(format!("Can't find {}", marker))?;
+    let mut bytes = vec![];
+    for line in lines[start..].iter() {
+        bytes.extend(hex_bytes(line));
+        if ascii(line).contains("16進数") {
+            break;
+        }
+    }
+    if bytes.len() != count {
+        return Err(format!("Expected {} bytes after {}, found {}", count, marker, bytes.len()));
+    }
+    Ok(value(&bytes))
+}
+
+// The lines after the one containing start, up to the one containing end.
+fn between<'a>(lines: &[&'a str], start: &str, end: &str) -> Result<Vec<&'a str>, String> {
+    let first = lines.iter().position(|line| line.contains(start)).ok_or(format!("Can't find {}", start))?;
+    let last = lines[first..].iter().position(|line| line.contains(end)).ok_or(format!("Can't find {}", end))?;
+    Ok(lines[first + 1..first + last].to_vec())
+}
+
+// "Ｋ12＝ 1B D4	Ｋ13＝ 24 55	..."
+fn parse_subkeys(lines: &[&str]) -> Result<Vec<u16>, String> {
+    let mut subkeys: Vec<(usize, u16)> = vec![];
+    for piece in lines.iter().flat_map(|line| line.split('\t')) {
+        if let Some((name, bytes)) = piece.split_once('＝') {
+            let name = ascii(name);
+            let idx = name.trim().trim_start_matches('Ｋ').parse::<usize>().map_err(|err| format!("Bad subkey name {}: {}", name, err))?;
+            subkeys.push((idx, value(&hex_bytes(bytes)) as u16));
+        }
+    }
+    subkeys.sort();
+    if subkeys.iter().enumerate().any(|(idx, (name, _))| idx != *name) {
+        return Err("The extended key isn't K0, K1, K2, ...".to_string());
+    }
+    Ok(subkeys.into_iter().map(|(_, subkey)| subkey).collect())
+}
+
+// "	1	F9 7F 1B 21	4C 36 67 CD	75 19	55 5C FD 7C"
+fn parse_rows(lines: &[&str]) -> Result<Vec<Row>, String> {
+    let mut rows = vec![];
+    for line in lines.iter() {
+        let fields: Vec<&str> = line.split('\t').map(|field| field.trim()).filter(|field| !field.is_empty()).collect();
+        let Some(Ok(round)) = fields.first().map(|field| field.parse::<usize>()) else {
+            continue;
+        };
+        let numbers: Vec<u128> = fields[1..].iter().map(|field| value(&hex_bytes(field))).collect();
+        match numbers[..] {
+            [left, right] => rows.push(Row {round, left: left as u32, right: right as u32, subkey: None, f_output: None}),
+            [left, right, subkey, f_output] => rows.push(Row {round, left: left as u32, right: right as u32, subkey: Some(subkey as u16), f_output: Some(f_output as u32)}),
+            _ => return Err(format!("Can't read round {}: {}", round, line)),
+        }
+    }
+    if rows.iter().enumerate().any(|(idx, row)| idx != row.round) {
+        return Err("The rounds aren't 0, 1, 2, ...".to_string());
+    }
+    Ok(rows)
+}
+
+// Section 6 of call-5.
+pub fn parse(text: &str) -> Result<Published, String> {
+    let lines: Vec<&str> = text.lines().collect();
+    let subkeys = parse_subkeys(&between(&lines, "この手続きを続け", "６．４")?)?;
+    let rows = parse_rows(&between(&lines, "処理ステージ", "後処理")?)?;
+    if rows.len() < 2 || subkeys.len() != rows.len() - 1 + 8 {
+        return Err(format!("{} rounds don't go with {} subkeys", rows.len().max(1) - 1, subkeys.len()));
+    }
+    Ok(Published {
+        key: hex_after(&lines, "鍵ブロックＫは", 16)?,
+        plaintext: hex_after(&lines, "平文Ｐは", 8)? as u64,
+        subkeys,
+        whitened: hex_after(&lines, "（Ｌ0，Ｒ0）A（Ｋ", 8)? as u64,
+        mixed: hex_after(&lines, "（Ｌ0，Ｒ0）A（φ，Ｌ0）", 8)? as u64,
+        rows,
+        swapped: hex_after(&lines, "を交換する", 8)? as u64,
+        unmixed: hex_after(&lines, "A (φ，R", 8)? as u64,
+        ciphertext: hex_after(&lines, "最終結果", 8)? as u64,
+    })
+}
+
+// The test vectors at the end: for each KEY, its (PT, CT) pairs.
+pub fn test_vectors(text: &str) -> Vec<(u128, Vec<(u64, u64)>)> {
+    let mut vectors: Vec<(u128, Vec<(u64, u64)>)> = vec![];
+    for line in text.lines() {
+        if let Some(key) = line.strip_prefix("KEY = ") {
+            if let Ok(key) = u128::from_str_radix(key.trim(), 16) {
+                vectors.push((key, vec![]));
+            }
+        }
+        else if let (Some((pt, ct)), Some((_, pairs))) = (line.split_once(','), vectors.last_mut()) {
+            let pt = u64::from_str_radix(pt.trim().trim_start_matches("PT:").trim(), 16);
+            let ct = u64::from_str_radix(ct.trim().trim_start_matches("CT:").trim(), 16);
+            if let (Ok(pt), Ok(ct)) = (pt, ct) {
+                pairs.push((pt, ct));
+            }
+        }
+    }
+    vectors
+}
+
+// One published value and ours.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    pub name: String,
+    pub published: String,
+    pub ours: String,
+}
+
+impl Step {
+    pub fn matches(&self) -> bool {
+        self.published == self.ours
+    }
+
+    // Call-5 has it wrong, and we have what it should have said.
+    pub fn misprint(&self) -> bool {
+        KNOWN_MISPRINTS.iter().any(|(name, published, ours)| self.name == *name && self.published == *published && self.ours == *ours)
+    }
+}
+
+fn step(name: String, published: u64, ours: u64, count: usize) -> Step {
+    Step {name, published: bytes(published, count), ours: bytes(ours, count)}
+}
+
+// Every published value next to ours, in the order the document gives them:
+// the extended key, the preprocessing, the round table, then the postprocessing.
+pub fn compare(published: &Published, subkeys: &[u16], trace: &Trace) -> Vec<Step> {
+    let n = published.rounds();
+    let missing = |name: String, published: String| Step {name, published, ours: "-".to_string()};
+    let mut steps: Vec<Step> = published.subkeys.iter().enumerate().map(|(idx, subkey)| match subkeys.get(idx) {
+        Some(ours) => step(format!("K{}", idx), *subkey as u64, *ours as u64, 2),
+        None => missing(format!("K{}", idx), bytes(*subkey as u64, 2)),
+    }).collect();
+    steps.push(step(format!("(L0, R0) ^ (K{}..K{})", n, n + 3), published.whitened, trace.whitened, 8));
+    steps.push(step("(L0, R0) ^ (0, L0)".to_string(), published.mixed, trace.mixed, 8));
+    for row in published.rows.iter().skip(1) {
+        let Some(ours) = trace.steps.get(row.round - 1) else {
+            steps.push(missing(format!("L{}", row.round), bytes(row.left as u64, 4)));
+            continue;
+        };
+        if let Some(subkey) = row.subkey {
+            steps.push(step(format!("K{}", row.round - 1), subkey as u64, ours.subkey as u64, 2));
+        }
+        if let Some(f_output) = row.f_output {
+            steps.push(step(format!("f(R{}, K{})", row.round - 1, row.round - 1), f_output as u64, ours.f_output as u64, 4));
+        }
+        steps.push(step(format!("L{}", row.round), row.left as u64, ours.left as u64, 4));
+        steps.push(step(format!("R{}", row.round), row.right as u64, ours.right as u64, 4));
+    }
+    steps.push(step(format!("(R{}, L{})", n, n), published.swapped, trace.swapped, 8));
+    steps.push(step(format!("(R{}, L{}) ^ (0, R{})", n, n, n), published.unmixed, trace.unmixed, 8));
+    steps.push(step("C".to_string(), published.ciphertext, trace.ciphertext, 8));
+    steps
+}
+
+// Our FEAL-NX with the published key and plaintext, against the published values.
+pub fn explain(published: &Published) -> Vec<Step> {
+    let feal = Feal::new_nx(published.key, published.rounds());
+    compare(published, &feal.encryption, &encrypt_traced(&feal, published.plaintext))
+}
+
+// The first step we get wrong (the known misprints don't count).
+pub fn first_difference(steps: &[Step]) -> Option<usize> {
+    steps.iter().position(|step| !step.matches() && !step.misprint())
+}
+
+// The side by side table, with the first difference marked.
+pub fn table(steps: &[Step]) -> String {
+    let first = first_difference(steps);
+    let mut lines = vec![format!("{:<28} {:<24} {}", "", "call-5", "ours")];
+    for (idx, step) in steps.iter().enumerate() {
+        let mark = match (Some(idx) == first, step.matches(), step.misprint()) {
+            (true, _, _) => "<-- first difference",
+            (false, false, true) => "misprint in call-5",
+            (false, false, false) => "x",
+            (false, true, _) => "",
+        };
+        lines.push(format!("{:<28} {:<24} {:<24} {}", step.name, step.published, step.ours, mark).trim_end().to_string());
+    }
+    let differences = steps.iter().filter(|step| !step.matches() && !step.misprint()).count();
+    let misprints = steps.iter().filter(|step| step.misprint()).count();
+    lines.push(String::new());
+    lines.push(match (first, misprints) {
+        (Some(idx), _) => format!("{} of {} values differ, starting at {}", differences, steps.len(), steps[idx].name),
+        (None, 0) => format!("All {} values match", steps.len()),
+        (None, _) => format!("{} of {} values match, and the rest are known misprints in call-5", steps.len() - misprints, steps.len()),
+    });
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::feal::keygen_rounds;
+
+    #[test]
+    fn parses_section_6() {
+        let published = parse(CALL5).unwrap();
+        assert_eq!(published.key, 0x0123456789abcdef0123456789abcdef);
+        assert_eq!(published.plaintext, 0);
+        assert_eq!(published.rounds(), 32);
+        assert_eq!(published.subkeys.len(), 40);
+        assert_eq!((published.subkeys[0], published.subkeys[39]), (0x7519, 0x683a));
+        assert_eq!(published.whitened, 0x196a9ab1e0158190);
+        assert_eq!(published.mixed, 0x196a9ab1f97f1b21);
+        assert_eq!(published.rows[1], Row {round: 1, left: 0xf97f1b21, right: 0x4c3667cd, subkey: Some(0x7519), f_output: Some(0x555cfd7c)});
+        assert_eq!(published.swapped, 0x03e932d4932ddf16);
+        assert_eq!(published.unmixed, 0x03e932d490c4edc2);
+        assert_eq!(published.ciphertext, 0x9c9b54973df685f8);
+    }
+
+    // Everything but the one misprint: call-5 has f(R25, K25) = D7 0D 85 FA, but its own
+    // R26 = L25 ^ f = C6 58 51 F1 ^ B7 0D 85 FA = 71 55 D4 0B says it's B7, like ours.
+    #[test]
+    fn matches_call5() {
+        let steps = explain(&parse(CALL5).unwrap());
+        let differences: Vec<&Step> = steps.iter().filter(|step| !step.matches()).collect();
+        assert_eq!(differences, [&Step {name: "f(R25, K25)".to_string(), published: "D7 0D 85 FA".to_string(), ours: "B7 0D 85 FA".to_string()}], "{}", table(&steps));
+        assert!(differences[0].misprint());
+        assert_eq!(first_difference(&steps), None);
+        let table = table(&steps);
+        assert!(table.contains("D7 0D 85 FA              B7 0D 85 FA              misprint in call-5"), "{}", table);
+        assert!(table.ends_with(&format!("{} of {} values match, and the rest are known misprints in call-5", steps.len() - 1, steps.len())), "{}", table);
+    }
+
+    // FEAL-N's key schedule (no KR) is already wrong at K0, and says so.
+    #[test]
+    fn flags_the_first_difference() {
+        let published = parse(CALL5).unwrap();
+        let subkeys = keygen_rounds(0x01234567, 0x89abcdef, 32);
+        let trace = encrypt_traced(&Feal::from_subkeys(subkeys.clone(), 32), 0);
+        let steps = compare(&published, &subkeys, &trace);
+        assert_eq!(first_difference(&steps), Some(0));
+        assert!(table(&steps).contains("K0                           75 19"));
+
+        // And with the right extended key but a different one used in round 5, it's round 5's K4.
+        let mut subkeys = published.subkeys.clone();
+        subkeys[4] ^= 1;
+        let trace = encrypt_traced(&Feal::from_subkeys(subkeys, 32), 0);
+        let steps = compare(&published, &published.subkeys, &trace);
+        assert_eq!(steps[first_difference(&steps).unwrap()].name, "K4");
+    }
+
+    #[test]
+    fn test_vectors() {
+        let vectors = super::test_vectors(CALL5);
+        assert_eq!(vectors.len(), 10);
+        for (key, pairs) in vectors.iter() {
+            assert_eq!(pairs.len(), 4096);
+            let feal = Feal::new_nx(*key, 32);
+            for (plaintext, ciphertext) in pairs.iter() {
+                assert_eq!(feal.encrypt(*plaintext), *ciphertext, "key {:032x}, plaintext {:016x}", key, plaintext);
+                assert_eq!(feal.decrypt(*ciphertext), *plaintext);
+            }
+        }
+    }
+}
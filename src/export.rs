@@ -0,0 +1,175 @@
+// vim: expandtab shiftwidth=4 tabstop=4:
+
+/* Write the computation graph out for other tools: Graphviz DOT, GraphML
+ * (what graph/graph.py used to leave behind as graph.graphml) and Mermaid.
+ * If we're handed the values from graph::eval, every node also gets
+ * its value pair and their difference, so the diagrams can go straight into
+ * papers and slides.
+ */
+
+use crate::graph::Node;
+use crate::hexstr;
+
+// (value1, value2, difference) for the node, if we have values at all.
+fn annotation(node: &Node, values: Option<&[(u64, u64)]>) -> Option<(String, String, String)> {
+    values.map(|values| {
+        let (value1, value2) = values[node.id as usize];
+        (hexstr(value1, node.bitsize), hexstr(value2, node.bitsize), hexstr(value1 ^ value2, node.bitsize))
+    })
+}
+
+fn is_copy(node: &Node) -> bool {
+    node.label().starts_with("copy")
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+pub fn to_dot(nodes: &[Node], values: Option<&[(u64, u64)]>) -> String {
+    // Positions are pinned, so lay it out with: neato -n2 -Tsvg graph.dot
+    let mut lines = vec![
+        "digraph feal {".to_string(),
+        "    node [shape=circle, style=filled, fontname=\"Arial\", fontsize=10];".to_string(),
+    ];
+    for node in nodes {
+        let pos = format!("{},{}!", node.x, -node.y);
+        let attrs = match annotation(node, values) {
+            _ if is_copy(node) => "shape=point, width=0.05".to_string(),
+            Some((value1, value2, difference)) => format!("shape=box, style=\"rounded,filled\", label=\"{}\\n{}\\n{}\\nΔ {}\"", node.label(), value1, value2, difference),
+            None => format!("label=\"{}\"", node.label()),
+        };
+        lines.push(format!("    n{} [{}, fillcolor=\"{}\", pos=\"{}\"];", node.id, attrs, node.color, pos));
+    }
+    for node in nodes {
+        for (name, src) in node.compgraph.inputs() {
+            lines.push(format!("    n{} -> n{} [label=\"{}\"];", src, node.id, name));
+        }
+    }
+    lines.push("}".to_string());
+    lines.join("\n")
+}
+
+pub fn to_graphml(nodes: &[Node], values: Option<&[(u64, u64)]>) -> String {
+    // The key ids are the attribute names, the same as translate_keys in graph/graph.py did.
+    let mut lines = vec![
+        "<?xml version='1.0' encoding='UTF-8'?>".to_string(),
+        "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">".to_string(),
+    ];
+    let mut keys = vec![("label", "string"), ("color", "string"), ("x", "double"), ("y", "double"), ("size", "double"), ("bitsize", "int")];
+    if values.is_some() {
+        keys.extend([("value1", "string"), ("value2", "string"), ("difference", "string")]);
+    }
+    for (name, kind) in keys.iter() {
+        lines.push(format!("  <key id=\"{}\" for=\"node\" attr.name=\"{}\" attr.type=\"{}\" />", name, name, kind));
+    }
+    lines.push("  <key id=\"edgelabel\" for=\"edge\" attr.name=\"label\" attr.type=\"string\" />".to_string());
+    lines.push("  <graph id=\"feal\" edgedefault=\"directed\">".to_string());
+    for node in nodes {
+        lines.push(format!("    <node id=\"{}\">", node.id));
+        let mut data = vec![
+            ("label", xml_escape(&node.label())),
+            ("color", node.color.clone()),
+            ("x", node.x.to_string()),
+            ("y", node.y.to_string()),
+            ("size", node.size.to_string()),
+            ("bitsize", node.bitsize.to_string()),
+        ];
+        if let Some((value1, value2, difference)) = annotation(node, values) {
+            data.extend([("value1", value1), ("value2", value2), ("difference", difference)]);
+        }
+        for (key, value) in data {
+            lines.push(format!("      <data key=\"{}\">{}</data>", key, value));
+        }
+        lines.push("    </node>".to_string());
+    }
+    for node in nodes {
+        for (name, src) in node.compgraph.inputs() {
+            lines.push(format!("    <edge source=\"{}\" target=\"{}\">", src, node.id));
+            lines.push(format!("      <data key=\"edgelabel\">{}</data>", name));
+            lines.push("    </edge>".to_string());
+        }
+    }
+    lines.push("  </graph>".to_string());
+    lines.push("</graphml>".to_string());
+    lines.join("\n")
+}
+
+pub fn to_mermaid(nodes: &[Node], values: Option<&[(u64, u64)]>) -> String {
+    let mut lines = vec!["flowchart TD".to_string()];
+    for node in nodes {
+        let text = match annotation(node, values) {
+            Some((value1, value2, difference)) => format!("{}<br/>{}<br/>{}<br/>Δ {}", node.label(), value1, value2, difference),
+            None => node.label(),
+        };
+        // Copies are just a fork in the wire, so draw them small.
+        if is_copy(node) {
+            lines.push(format!("    n{}((\"{}\"))", node.id, text));
+        }
+        else {
+            lines.push(format!("    n{}([\"{}\"])", node.id, text));
+        }
+    }
+    for node in nodes {
+        for (name, src) in node.compgraph.inputs() {
+            lines.push(format!("    n{} -->|{}| n{}", src, name, node.id));
+        }
+    }
+    for node in nodes {
+        lines.push(format!("    style n{} fill:{}", node.id, node.color));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::feal::Key;
+    use crate::graph::{eval, feal_graph};
+
+    fn edges(nodes: &[Node]) -> usize {
+        nodes.iter().map(|node| node.compgraph.inputs().len()).sum()
+    }
+
+    #[test]
+    fn a_line_per_node_and_edge() {
+        let nodes = feal_graph(4);
+        let dot = to_dot(&nodes, None);
+        let node_line = |line: &&str| line.trim_start().strip_prefix('n').is_some_and(|rest| rest.starts_with(|c: char| c.is_ascii_digit()));
+        assert_eq!(dot.lines().filter(node_line).filter(|line| !line.contains(" -> ")).count(), nodes.len());
+        assert_eq!(dot.lines().filter(|line| line.contains(" -> ")).count(), edges(&nodes));
+
+        let graphml = to_graphml(&nodes, None);
+        assert_eq!(graphml.matches("<node id=").count(), nodes.len());
+        assert_eq!(graphml.matches("<edge source=").count(), edges(&nodes));
+
+        let mermaid = to_mermaid(&nodes, None);
+        assert_eq!(mermaid.lines().filter(|line| line.contains("((\"") || line.contains("([\"")).count(), nodes.len());
+        assert_eq!(mermaid.lines().filter(|line| line.contains(" -->|")).count(), edges(&nodes));
+    }
+
+    // What --annotate writes is what graph::eval comes up with.
+    #[test]
+    fn annotations_match_eval() {
+        let key = 0x0123456789abcdef;
+        let nodes = feal_graph(4);
+        let subkeys = Key::N(u64::to_be_bytes(key)).subkeys(4);
+        let values = eval(&nodes, key, &subkeys, 0x5a5a5a5a5a5a5a5a, 0x8080000080800000);
+        let dot = to_dot(&nodes, Some(&values));
+        let graphml = to_graphml(&nodes, Some(&values));
+        let mermaid = to_mermaid(&nodes, Some(&values));
+        for node in &nodes {
+            let (value1, value2) = values[node.id as usize];
+            let (value1, value2, difference) = (hexstr(value1, node.bitsize), hexstr(value2, node.bitsize), hexstr(value1 ^ value2, node.bitsize));
+            let block = graphml.split("<node id=").nth(node.id as usize + 1).unwrap();
+            assert!(block.contains(&format!("<data key=\"value1\">{}</data>\n      <data key=\"value2\">{}</data>\n      <data key=\"difference\">{}</data>", value1, value2, difference)), "node {}", node.id);
+            let text = format!("{}<br/>{}<br/>{}<br/>Δ {}", node.label(), value1, value2, difference);
+            let line = if is_copy(node) { format!("    n{}((\"{}\"))", node.id, text) } else { format!("    n{}([\"{}\"])", node.id, text) };
+            assert!(mermaid.lines().any(|candidate| candidate == line), "node {}", node.id);
+            // DOT draws copies as points, without a label.
+            let label = format!("label=\"{}\\n{}\\n{}\\nΔ {}\"", node.label(), value1, value2, difference);
+            assert_eq!(dot.contains(&format!("    n{} [shape=box, style=\"rounded,filled\", {}", node.id, label)), !is_copy(node), "node {}", node.id);
+        }
+    }
+}
@@ -6,23 +6,57 @@
  * Actually, "Applied Cryptography" by Bruce Schneier has a section on FEAL in Chapter 13.4 (pp 308 on my copy).
  */
 
-pub fn u64tou32(inp: u64) -> (u32, u32) {
+// The ">> 0" and "+ 0" are there to keep the byte lanes lined up.
+#![allow(clippy::identity_op)]
+
+/* None of this needs std, so it builds for bare metal boards too (see the
+ * features in Cargo.toml). Without the "alloc" feature you get everything
+ * but the Vec based key schedules and Feal; keygen_nx_into and fealn_raw
+ * do the same job with an array you bring yourself. The cipher itself
+ * is all const fn, so a fixed schedule can be worked out at compile time:
+ *     const SCHEDULE: [u16; 16] = keygen(0x01234567, 0x89abcdef);
+ * const fn can't use for loops, hence all the whiles.
+ *
+ * FEAL is a toy, but the key handling here is done the way it should be
+ * for a real cipher: with the "zeroize" feature, Subkeys, Key, Subkey and
+ * Feal wipe themselves when they're dropped, their == is constant time
+ * (ct_eq) and their Debug doesn't print the key. keygen, encrypt and
+ * decrypt work on plain arrays so that they can stay const; Subkeys::new,
+ * encrypt_secret and decrypt_secret are the same things for when the key
+ * is a secret, and they only ever lend the schedule out, so there's no
+ * copy of it left lying around to wipe.
+ */
+
+use core::fmt;
+use core::ops::Deref;
+use core::str::FromStr;
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "alloc")]
+use alloc::{vec, vec::Vec};
+#[cfg(feature = "zeroize")]
+use zeroize::Zeroize;
+
+// The packing helpers are big-endian throughout: the first of the smaller pieces
+// is the most significant, so u64tou32 gives (left, right). Outside this crate,
+// use Block, Key and Subkey below, which say so in their byte order.
+
+pub(crate) const fn u64tou32(inp: u64) -> (u32, u32) {
     let a = ((inp >> 32) & 0x00ffffffff) as u32;
     let b = ((inp >>  0) & 0x00ffffffff) as u32;
     (a, b)
 }
 
-pub fn u32tou64(a: u32, b:u32) -> u64 {
-    let number = ((a as u64) << 32) | b as u64;
-    number
+pub(crate) const fn u32tou64(a: u32, b:u32) -> u64 {
+    ((a as u64) << 32) | b as u64
 }
 
-pub fn u16tou64(a: u16, b:u16, c: u16, d: u16) -> u64 {
-    let number = ((a as u64) << 48) | ((b as u64) << 32) | ((c as u64) << 16) | d as u64;
-    number
+pub(crate) const fn u16tou64(a: u16, b:u16, c: u16, d: u16) -> u64 {
+    ((a as u64) << 48) | ((b as u64) << 32) | ((c as u64) << 16) | d as u64
 }
 
-pub fn u32tou8(inp: u32) -> (u8, u8, u8, u8) {
+pub(crate) const fn u32tou8(inp: u32) -> (u8, u8, u8, u8) {
     let a = ((inp >> 24) & 0x00ff) as u8;
     let b = ((inp >> 16) & 0x00ff) as u8;
     let c = ((inp >>  8) & 0x00ff) as u8;
@@ -31,37 +65,35 @@ pub fn u32tou8(inp: u32) -> (u8, u8, u8, u8) {
     (a, b, c, d)
 }
 
-pub fn u8tou32(a: u8, b: u8, c: u8, d: u8) -> u32 {
-    let number = ((a as u32) << 24) | ((b as u32) << 16) | ((c as u32) << 8) | d as u32;
-    number
+pub(crate) const fn u8tou32(a: u8, b: u8, c: u8, d: u8) -> u32 {
+    ((a as u32) << 24) | ((b as u32) << 16) | ((c as u32) << 8) | d as u32
 }
 
-pub fn u32tou16(inp: u32) -> (u16, u16) {
+pub(crate) const fn u32tou16(inp: u32) -> (u16, u16) {
     let a = ((inp >> 16) & 0x00ffff) as u16;
     let b = ((inp >>  0) & 0x00ffff) as u16;
     (a, b)
 }
 
-pub fn u16tou8(inp: u16) -> (u8, u8) {
+pub(crate) const fn u16tou8(inp: u16) -> (u8, u8) {
     let a = ((inp >> 8) & 0x00ff) as u8;
     let b = ((inp >> 0) & 0x00ff) as u8;
     (a, b)
 }
 
-pub fn gx(x: u8, a: u8, b: u8) -> u8 {
+pub const fn gx(x: u8, a: u8, b: u8) -> u8 {
     // Compared against feal-8 from https://www.schneier.com/wp-content/uploads/2015/03/FEAL8-WI-2.zip
     // and verified to be working.
     // gx(a, b) = rotate left two bits((a+b+x) mod 256)
     // gx corresponds to Sd in FEAL8
     let int = a.wrapping_add(b).wrapping_add(x);
-    let rot = ((int << 2) & 0xfc) | ((int & 0xc0) >> 6);
-    rot
+    ((int << 2) & 0xfc) | ((int & 0xc0) >> 6)
 }
 
-pub fn g0(inp1: u8, inp2: u8) -> u8 { gx(0, inp1, inp2) }
-pub fn g1(inp1: u8, inp2: u8) -> u8 { gx(1, inp1, inp2) }
+pub const fn g0(inp1: u8, inp2: u8) -> u8 { gx(0, inp1, inp2) }
+pub const fn g1(inp1: u8, inp2: u8) -> u8 { gx(1, inp1, inp2) }
 
-pub fn fyoutube(a: u8, b: u8, c: u8, d: u8) -> (u8, u8, u8, u8) {
+pub const fn fyoutube(a: u8, b: u8, c: u8, d: u8) -> (u8, u8, u8, u8) {
     // "Applied Cryptography" Bruce Schneier 13.4 Figure 13.4
     // This is _actually_ from this YouTube video: https://www.youtube.com/watch?v=xav-GUO_o4s#t=965
     // To really translate this from Bruce Schneier's diagram:
@@ -89,7 +121,7 @@ pub fn fyoutube(a: u8, b: u8, c: u8, d: u8) -> (u8, u8, u8, u8) {
     (ap, bp, cp, dp)
 }
 
-pub fn f(b: u16, a: u32) -> u32 {
+pub const fn f(b: u16, a: u32) -> u32 {
     // "Applied Cryptography" Bruce Schneier 13.4 Figure 13.4
     // b0, b1 are the "keys"
     // a0, a1, a2, a3 are something. (Look at the diagram)
@@ -111,7 +143,8 @@ pub fn f(b: u16, a: u32) -> u32 {
     u8tou32(ap, bp, cp, dp)
 }
 
-pub fn fk(a0: u8, a1: u8, a2: u8, a3: u8, b0: u8, b1: u8, b2: u8, b3: u8) -> (u8, u8, u8, u8) {
+#[allow(clippy::too_many_arguments)]
+pub const fn fk(a0: u8, a1: u8, a2: u8, a3: u8, b0: u8, b1: u8, b2: u8, b3: u8) -> (u8, u8, u8, u8) {
     // "Applied Cryptography" Bruce Schneier 13.4 Figure 13.6
     let v1 = a0 ^ a1;
     let v2 = a2 ^ a3;
@@ -128,7 +161,7 @@ pub fn fk(a0: u8, a1: u8, a2: u8, a3: u8, b0: u8, b1: u8, b2: u8, b3: u8) -> (u8
     (f1, f2, f3, f4)
 }
 
-pub fn fk32(a: u32, b: u32) -> u32 {
+pub const fn fk32(a: u32, b: u32) -> u32 {
     // Compared against feal-8 from https://www.schneier.com/wp-content/uploads/2015/03/FEAL8-WI-2.zip
     // and verified to be working.
     // This function (fk32) corresponds to fK in that code.
@@ -139,14 +172,14 @@ pub fn fk32(a: u32, b: u32) -> u32 {
     u8tou32(o0, o1, o2, o3)
 }
 
-pub fn keyround(a0: u32, b0: u32, d0: u32) -> (u16, u16, u32, u32, u32) {
+pub const fn keyround(a0: u32, b0: u32, d0: u32) -> (u16, u16, u32, u32, u32) {
     let v = b0 ^ d0;
     let k01 = fk32(a0, v);
     let (k0, k1) = u32tou16(k01);
     (k0, k1, b0, k01, a0)
 }
 
-pub fn keygen(a: u32, b: u32) -> [u16; 16] {
+pub const fn keygen(a: u32, b: u32) -> [u16; 16] {
     // Key generation seems to differ between
     // https://www.schneier.com/wp-content/uploads/2015/03/FEAL8-WI-2.zip
     // and here.
@@ -158,7 +191,8 @@ pub fn keygen(a: u32, b: u32) -> [u16; 16] {
     let mut subkeys: [u16; 16] = [0_u16; 16];
 
     let (mut a, mut b) = (a, b);
-    for idx in 0..8 {
+    let mut idx = 0;
+    while idx < 8 {
         let (k0, k1, ap, bp, dp) = keyround(a, b, d);
 
         // a in our code is U1 in the reference.
@@ -171,68 +205,682 @@ pub fn keygen(a: u32, b: u32) -> [u16; 16] {
         // U2, U1, U0 = U1, U0, U
         // d, a, b = U1, U0, U
         (a, b, d) = (ap, bp, dp);
+        idx += 1;
     }
 
-    [
-        subkeys[0],
-        subkeys[1],
-        subkeys[2],
-        subkeys[3],
-        subkeys[4],
-        subkeys[5],
-        subkeys[6],
-        subkeys[7],
-        subkeys[8],
-        subkeys[9],
-        subkeys[10],
-        subkeys[11],
-        subkeys[12],
-        subkeys[13],
-        subkeys[14],
-        subkeys[15]
-    ]
-}
-
-pub fn single_round_encrypt(k: u16, left: u32, right: u32) -> (u32, u32) {
+    subkeys
+}
+
+#[cfg(feature = "alloc")]
+pub fn keygen_rounds(a: u32, b: u32, rounds: usize) -> Vec<u16> {
+    // The same schedule as keygen, but for FEAL-N, which needs rounds + 8 subkeys:
+    // k[0..rounds] for the rounds, then 4 each for the input and output whitening.
+    // keygen(a, b) is keygen_rounds(a, b, 8).
+    keygen_nx(u32tou64(a, b), 0, rounds)
+}
+
+#[cfg(feature = "alloc")]
+pub fn keygen_nx(kl: u64, kr: u64, rounds: usize) -> Vec<u16> {
+    let mut subkeys = vec![0; rounds + 8];
+    keygen_nx_into(kl, kr, &mut subkeys);
+    subkeys
+}
+
+pub const fn keygen_nx_into(kl: u64, kr: u64, subkeys: &mut [u16]) {
+    // FEAL-NX, with its 128 bit key (kl, kr): kl starts off a and b like before,
+    // and kr is xored into every keyround's b ^ d, as Q = kr1 ^ kr2, kr1, kr2,
+    // kr1 ^ kr2, kr1, ... (misc/call-5-utf8.txt section 4). FEAL-N is kr = 0.
+    // Fills all of subkeys, so FEAL-<rounds> wants rounds + 8 of them, and with
+    // an odd number the last one would never get filled in.
+    assert!(subkeys.len().is_multiple_of(2), "FEAL-N needs an even number of subkeys");
+    let (kr1, kr2) = u64tou32(kr);
+    let (mut a, mut b) = u64tou32(kl);
+    let mut d: u32 = 0;
+
+    let mut idx = 0;
+    while idx < subkeys.len() / 2 {
+        let q = match idx % 3 {
+            0 => kr1 ^ kr2,
+            1 => kr1,
+            _ => kr2,
+        };
+        let (k0, k1, _, bp, dp) = keyround(a, b ^ q, d);
+        subkeys[2 * idx] = k0;
+        subkeys[2 * idx + 1] = k1;
+        // keyround hands back b ^ q as the next a, but it's b that moves along.
+        (a, b, d) = (b, bp, dp);
+        idx += 1;
+    }
+}
+
+pub const fn single_round_encrypt(k: u16, left: u32, right: u32) -> (u32, u32) {
     let (left, right) = (left, right);
     let intermediate = f(k, right);
     let (newleft, newright) = (right, left ^ intermediate);
     (newleft, newright)
 }
 
-pub fn feal4_raw(k: [u16; 16], input: u64) -> u64 {
+pub const fn feal4_raw(k: [u16; 16], input: u64) -> u64 {
     // "Applied Cryptography" Bruce Schneier 13.4 Figure 13.3
     // input: output
     let v1 = input ^ u16tou64(k[8], k[9], k[10], k[11]); // combined
     let (mut left, mut right) = u64tou32(v1);
 
-    right = left ^ right;
+    right ^= left;
 
-    for x in 0..8 {
-        (left, right) = single_round_encrypt(k[x], left, right);
+    let mut idx = 0;
+    while idx < 8 {
+        (left, right) = single_round_encrypt(k[idx], left, right);
+        idx += 1;
     }
-    left = left ^ right;
+    left ^= right;
     let combined = u32tou64(right, left); // v1
     let output = combined ^ u16tou64(k[12], k[13], k[14], k[15]); // input
 
     output
 }
 
-pub fn encrypt(keybits: u64, plaintext: u64) -> u64 {
+pub const fn fealn_raw(k: &[u16], rounds: usize, input: u64) -> u64 {
+    // feal4_raw for FEAL-N: k[0..rounds] for the rounds, then k[rounds..rounds+4]
+    // and k[rounds+4..rounds+8] whiten the input and output, the way keygen_rounds lays them out.
+    // fealn_raw(&k, 8, input) is feal4_raw(k, input).
+    let v1 = input ^ u16tou64(k[rounds], k[rounds + 1], k[rounds + 2], k[rounds + 3]);
+    let (mut left, mut right) = u64tou32(v1);
+
+    right ^= left;
+
+    let mut idx = 0;
+    while idx < rounds {
+        (left, right) = single_round_encrypt(k[idx], left, right);
+        idx += 1;
+    }
+    left ^= right;
+    let combined = u32tou64(right, left);
+    combined ^ u16tou64(k[rounds + 4], k[rounds + 5], k[rounds + 6], k[rounds + 7])
+}
+
+// a == b, taking the same time wherever they differ (the lengths aren't secret, though).
+pub fn ct_eq<T: Copy + Into<u64>>(a: &[T], b: &[T]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let difference = a.iter().zip(b.iter()).fold(0u64, |acc, (x, y)| acc | ((*x).into() ^ (*y).into()));
+    // So the compiler can't turn the fold back into an early exit.
+    core::hint::black_box(difference) == 0
+}
+
+// keygen's 16 subkeys, for when they're secret: wiped on drop with the zeroize
+// feature, and they don't show up in Debug. Derefs to the array.
+#[derive(Clone)]
+pub struct Subkeys([u16; 16]);
+
+impl Subkeys {
+    pub fn new(a: u32, b: u32) -> Subkeys {
+        Subkeys(keygen(a, b))
+    }
+}
+
+impl From<[u16; 16]> for Subkeys {
+    fn from(subkeys: [u16; 16]) -> Subkeys {
+        Subkeys(subkeys)
+    }
+}
+
+impl Deref for Subkeys {
+    type Target = [u16; 16];
+
+    fn deref(&self) -> &[u16; 16] {
+        &self.0
+    }
+}
+
+impl PartialEq for Subkeys {
+    fn eq(&self, other: &Subkeys) -> bool {
+        ct_eq(&self.0, &other.0)
+    }
+}
+
+impl fmt::Debug for Subkeys {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "Subkeys(<redacted>)")
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for Subkeys {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+// Subkeys for any number of rounds, from Key::subkeys: like Subkeys, wiped on
+// drop with the zeroize feature and left out of Debug. Derefs to the slice.
+#[cfg(feature = "alloc")]
+#[derive(Clone)]
+pub struct Schedule(Vec<u16>);
+
+#[cfg(feature = "alloc")]
+impl Deref for Schedule {
+    type Target = [u16];
+
+    fn deref(&self) -> &[u16] {
+        &self.0
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl PartialEq for Schedule {
+    fn eq(&self, other: &Schedule) -> bool {
+        ct_eq(&self.0, &other.0)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl fmt::Debug for Schedule {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "Schedule(<redacted>)")
+    }
+}
+
+#[cfg(all(feature = "alloc", feature = "zeroize"))]
+impl Drop for Schedule {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+// Why a Block, Key or Subkey didn't parse.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseHexError {
+    // Something other than hex digits, spaces and a leading 0x.
+    NotHex,
+    // The wrong number of hex digits: how many there were and how many it wanted.
+    Length {digits: usize, expected: &'static str},
+}
+
+impl fmt::Display for ParseHexError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseHexError::NotHex => write!(formatter, "not a hexadecimal number"),
+            ParseHexError::Length {digits, expected} => write!(formatter, "expected {} hex digits, not {}", expected, digits),
+        }
+    }
+}
+
+impl core::error::Error for ParseHexError {}
+
+// The values of the hex digits in text, which can have spaces and one leading 0x
+// (or 0X), once we know they're all hex digits.
+fn hex_digits(text: &str) -> Result<impl Iterator<Item = u8> + Clone + '_, ParseHexError> {
+    let text = text.trim();
+    let text = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")).unwrap_or(text);
+    let digits = text.chars().filter(|c| !c.is_whitespace());
+    if !digits.clone().all(|c| c.is_ascii_hexdigit()) {
+        return Err(ParseHexError::NotHex);
+    }
+    Ok(digits.map(|c| c.to_digit(16).unwrap() as u8))
+}
+
+// "0123456789abcdef", "0x0123456789ABCDEF" or "01 23 45 67 89 AB CD EF" (the way
+// call-5 writes them) into N bytes, if the number of digits is allowed. Short
+// values get leading zeros.
+fn parse_hex<const N: usize>(text: &str, expected: &'static str, allowed: impl Fn(usize) -> bool) -> Result<[u8; N], ParseHexError> {
+    let digits = hex_digits(text)?;
+    let count = digits.clone().count();
+    if !allowed(count) || count > 2 * N {
+        return Err(ParseHexError::Length {digits: count, expected});
+    }
+    let mut bytes = [0u8; N];
+    for (idx, digit) in digits.enumerate() {
+        // Which nibble of bytes this digit is, counting from the top.
+        let nibble = 2 * N - count + idx;
+        bytes[nibble / 2] |= digit << (4 * (1 - nibble % 2));
+    }
+    Ok(bytes)
+}
+
+// Up to 16 digits as a u64, for the command line's --key 0123456789abcdef and --plaintext 0.
+pub fn parse_hex_u64(text: &str) -> Result<u64, ParseHexError> {
+    parse_hex(text, "1 to 16", |digits| (1..=16).contains(&digits)).map(u64::from_be_bytes)
+}
+
+// The same, for any number of bytes: "68 65 6c 6c 6f" or "68656c6c6f". There's
+// no telling where a short value would want its missing digit, so it's an error.
+#[cfg(feature = "alloc")]
+pub fn parse_hex_bytes(text: &str) -> Result<Vec<u8>, ParseHexError> {
+    let digits: Vec<u8> = hex_digits(text)?.collect();
+    if !digits.len().is_multiple_of(2) {
+        return Err(ParseHexError::Length {digits: digits.len(), expected: "an even number of"});
+    }
+    Ok(digits.chunks(2).map(|pair| (pair[0] << 4) | pair[1]).collect())
+}
+
+fn write_hex_bytes(formatter: &mut fmt::Formatter, bytes: &[u8]) -> fmt::Result {
+    bytes.iter().try_for_each(|byte| write!(formatter, "{:02x}", byte))
+}
+
+// A 64 bit block, in the order it goes over the wire: bytes 0..4 are L and
+// bytes 4..8 are R, and as a u64 it's big-endian, so byte 0 is the top byte.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Block(pub [u8; 8]);
+
+impl From<u64> for Block {
+    fn from(value: u64) -> Block {
+        Block(value.to_be_bytes())
+    }
+}
+
+impl From<Block> for u64 {
+    fn from(block: Block) -> u64 {
+        u64::from_be_bytes(block.0)
+    }
+}
+
+impl FromStr for Block {
+    type Err = ParseHexError;
+
+    fn from_str(text: &str) -> Result<Block, ParseHexError> {
+        parse_hex(text, "16", |digits| digits == 16).map(Block)
+    }
+}
+
+impl fmt::Display for Block {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write_hex_bytes(formatter, &self.0)
+    }
+}
+
+// A FEAL-N key (8 bytes) or a FEAL-NX one (16: KL then KR). Either way byte 0
+// is the most significant, so 0123456789abcdef is [0x01, 0x23, ..., 0xef].
+#[derive(Clone)]
+pub enum Key {
+    N([u8; 8]),
+    NX([u8; 16]),
+}
+
+impl Key {
+    // The rounds + 8 subkeys, laid out the way keygen_rounds does it.
+    #[cfg(feature = "alloc")]
+    pub fn subkeys(&self, rounds: usize) -> Schedule {
+        let mut subkeys = Schedule(vec![0; rounds + 8]);
+        self.subkeys_into(&mut subkeys.0);
+        subkeys
+    }
+
+    // The same, into all of subkeys (so rounds + 8 of them for FEAL-<rounds>).
+    pub fn subkeys_into(&self, subkeys: &mut [u16]) {
+        match self {
+            Key::N(bytes) => keygen_nx_into(u64::from_be_bytes(*bytes), 0, subkeys),
+            Key::NX(bytes) => {
+                let key = u128::from_be_bytes(*bytes);
+                keygen_nx_into((key >> 64) as u64, key as u64, subkeys)
+            },
+        }
+    }
+}
+
+impl PartialEq for Key {
+    fn eq(&self, other: &Key) -> bool {
+        match (self, other) {
+            (Key::N(a), Key::N(b)) => ct_eq(a, b),
+            (Key::NX(a), Key::NX(b)) => ct_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Key {}
+
+// Which kind of key, but not the key.
+impl fmt::Debug for Key {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Key::N(_) => write!(formatter, "Key::N(<redacted>)"),
+            Key::NX(_) => write!(formatter, "Key::NX(<redacted>)"),
+        }
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for Key {
+    fn drop(&mut self) {
+        match self {
+            Key::N(bytes) => bytes.zeroize(),
+            Key::NX(bytes) => bytes.zeroize(),
+        }
+    }
+}
+
+impl From<[u8; 8]> for Key {
+    fn from(bytes: [u8; 8]) -> Key {
+        Key::N(bytes)
+    }
+}
+
+impl From<[u8; 16]> for Key {
+    fn from(bytes: [u8; 16]) -> Key {
+        Key::NX(bytes)
+    }
+}
+
+// 16 hex digits for FEAL-N, 32 for FEAL-NX.
+impl FromStr for Key {
+    type Err = ParseHexError;
+
+    fn from_str(text: &str) -> Result<Key, ParseHexError> {
+        match parse_hex(text, "16 or 32", |digits| digits == 16) {
+            Err(ParseHexError::Length {..}) => parse_hex(text, "16 or 32", |digits| digits == 32).map(Key::NX),
+            result => result.map(Key::N),
+        }
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Key::N(bytes) => write_hex_bytes(formatter, bytes),
+            Key::NX(bytes) => write_hex_bytes(formatter, bytes),
+        }
+    }
+}
+
+// One 16 bit piece of the extended key, like call-5's K0 = 75 19.
+#[derive(Clone, Default)]
+pub struct Subkey(pub u16);
+
+impl PartialEq for Subkey {
+    fn eq(&self, other: &Subkey) -> bool {
+        ct_eq(&[self.0], &[other.0])
+    }
+}
+
+impl Eq for Subkey {}
+
+impl fmt::Debug for Subkey {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "Subkey(<redacted>)")
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for Subkey {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl From<u16> for Subkey {
+    fn from(value: u16) -> Subkey {
+        Subkey(value)
+    }
+}
+
+impl From<Subkey> for u16 {
+    fn from(subkey: Subkey) -> u16 {
+        subkey.0
+    }
+}
+
+impl FromStr for Subkey {
+    type Err = ParseHexError;
+
+    // Leading zeros are optional here, so "7519", "75 19" and "a" all work.
+    fn from_str(text: &str) -> Result<Subkey, ParseHexError> {
+        parse_hex(text, "1 to 4", |digits| (1..=4).contains(&digits)).map(|bytes| Subkey(u16::from_be_bytes(bytes)))
+    }
+}
+
+impl fmt::Display for Subkey {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write_hex_bytes(formatter, &self.0.to_be_bytes())
+    }
+}
+
+// The key schedule for FEAL-<rounds>, worked out once, for when there are lots of
+// blocks to do under the same key. Decryption is encryption with the round keys
+// reversed and the two whitening keys swapped, like decrypt below does for FEAL-8.
+#[cfg(feature = "alloc")]
+#[derive(Clone)]
+pub struct Feal {
+    pub rounds: usize,
+    pub encryption: Vec<u16>,
+    pub decryption: Vec<u16>,
+}
+
+// decryption is worked out from encryption, so that's all there is to compare.
+#[cfg(feature = "alloc")]
+impl PartialEq for Feal {
+    fn eq(&self, other: &Feal) -> bool {
+        self.rounds == other.rounds && ct_eq(&self.encryption, &other.encryption)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl fmt::Debug for Feal {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.debug_struct("Feal").field("rounds", &self.rounds).finish_non_exhaustive()
+    }
+}
+
+// Zeroes the subkeys and empties both lists.
+#[cfg(all(feature = "alloc", feature = "zeroize"))]
+impl Zeroize for Feal {
+    fn zeroize(&mut self) {
+        self.encryption.zeroize();
+        self.decryption.zeroize();
+    }
+}
+
+#[cfg(all(feature = "alloc", feature = "zeroize"))]
+impl Drop for Feal {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Feal {
+    pub fn new(keybits: u64, rounds: usize) -> Feal {
+        let (ka, kb) = u64tou32(keybits);
+        Feal::from_subkeys(keygen_rounds(ka, kb, rounds), rounds)
+    }
+
+    // FEAL-NX, key = kl << 64 | kr.
+    pub fn new_nx(key: u128, rounds: usize) -> Feal {
+        Feal::from_key(&Key::NX(key.to_be_bytes()), rounds)
+    }
+
+    pub fn from_key(key: &Key, rounds: usize) -> Feal {
+        // Straight into the Vec the Feal keeps (and wipes).
+        let mut encryption = vec![0; rounds + 8];
+        key.subkeys_into(&mut encryption);
+        Feal::from_subkeys(encryption, rounds)
+    }
+
+    // subkeys are laid out the way keygen_rounds does it.
+    pub fn from_subkeys(encryption: Vec<u16>, rounds: usize) -> Feal {
+        let mut decryption: Vec<u16> = encryption[..rounds].iter().rev().copied().collect();
+        decryption.extend_from_slice(&encryption[rounds + 4..rounds + 8]);
+        decryption.extend_from_slice(&encryption[rounds..rounds + 4]);
+        Feal {rounds, encryption, decryption}
+    }
+
+    pub fn encrypt(&self, plaintext: u64) -> u64 {
+        fealn_raw(&self.encryption, self.rounds, plaintext)
+    }
+
+    pub fn decrypt(&self, ciphertext: u64) -> u64 {
+        fealn_raw(&self.decryption, self.rounds, ciphertext)
+    }
+
+    // In place, on the block's bytes (see Block for the byte order).
+    pub fn encrypt_block(&self, block: &mut [u8; 8]) {
+        *block = self.encrypt(u64::from_be_bytes(*block)).to_be_bytes();
+    }
+
+    pub fn decrypt_block(&self, block: &mut [u8; 8]) {
+        *block = self.decrypt(u64::from_be_bytes(*block)).to_be_bytes();
+    }
+}
+
+pub const fn encrypt(keybits: u64, plaintext: u64) -> u64 {
     let (ka, kb) = u64tou32(keybits);
     let k = keygen(ka, kb);
     feal4_raw(k, plaintext)
 }
 
-pub fn decrypt(keybits: u64, ciphertext: u64) -> u64 {
-    let (ka, kb) = u64tou32(keybits);
-    let kraw = keygen(ka, kb);
 // k00 k01 k02 k03 k04 k05 k06 k07 k08 k09 k10 k11 k12 k13 k14 k15
 // k07 k06 k05 k04 k03 k02 k01 k00 k12 k13 k14 k15 k08 k09 k10 k11
-    let mapping : [usize; 16] = [7, 6, 5, 4, 3, 2, 1, 0, 12, 13, 14, 15, 8, 9, 10, 11];
+const DECRYPTION: [usize; 16] = [7, 6, 5, 4, 3, 2, 1, 0, 12, 13, 14, 15, 8, 9, 10, 11];
+
+pub const fn decrypt(keybits: u64, ciphertext: u64) -> u64 {
+    let (ka, kb) = u64tou32(keybits);
+    let kraw = keygen(ka, kb);
     let mut k : [u16; 16] = [0_u16; 16];
-    for (dstidx, srcidx) in mapping.iter().enumerate() {
-        k[dstidx] = kraw[*srcidx];
+    let mut dstidx = 0;
+    while dstidx < 16 {
+        k[dstidx] = kraw[DECRYPTION[dstidx]];
+        dstidx += 1;
     }
     feal4_raw(k, ciphertext)
 }
+
+// encrypt and decrypt for a secret key: the schedules are Subkeys, and fealn_raw
+// only borrows them, so they're wiped when they go (with the zeroize feature).
+pub fn encrypt_secret(keybits: u64, plaintext: u64) -> u64 {
+    let (ka, kb) = u64tou32(keybits);
+    let k = Subkeys::new(ka, kb);
+    fealn_raw(&k.0, 8, plaintext)
+}
+
+pub fn decrypt_secret(keybits: u64, ciphertext: u64) -> u64 {
+    let (ka, kb) = u64tou32(keybits);
+    let kraw = Subkeys::new(ka, kb);
+    let mut k = Subkeys([0_u16; 16]);
+    for (dstidx, srcidx) in DECRYPTION.iter().enumerate() {
+        k.0[dstidx] = kraw[*srcidx];
+    }
+    fealn_raw(&k.0, 8, ciphertext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Worked out by the compiler, so if these are wrong the tests don't build.
+    const SCHEDULE: [u16; 16] = keygen(0x01234567, 0x89abcdef);
+    const _: () = assert!(feal4_raw(SCHEDULE, 0) == 0xceef2c86f2490752);
+    const _: () = assert!(decrypt(0x0123456789abcdef, encrypt(0x0123456789abcdef, 0x5a5a5a5a5a5a5a5a)) == 0x5a5a5a5a5a5a5a5a);
+    // call-5's FEAL-NX example (N = 32, K = 0123456789ABCDEF0123456789ABCDEF).
+    const CALL5_SCHEDULE: [u16; 40] = {
+        let mut subkeys = [0; 40];
+        keygen_nx_into(0x0123456789abcdef, 0x0123456789abcdef, &mut subkeys);
+        subkeys
+    };
+    const _: () = assert!(CALL5_SCHEDULE[0] == 0x7519 && CALL5_SCHEDULE[39] == 0x683a);
+    const _: () = assert!(fealn_raw(&CALL5_SCHEDULE, 32, 0) == 0x9c9b54973df685f8);
+
+    #[test]
+    fn const_schedule() {
+        assert_eq!(SCHEDULE[0], 0xdf3b);
+        let mut subkeys = [0; 16];
+        Key::N(0x0123456789abcdef_u64.to_be_bytes()).subkeys_into(&mut subkeys);
+        assert_eq!(subkeys, SCHEDULE);
+    }
+
+    #[test]
+    fn hex_round_trips() {
+        let block: Block = "01 23 45 67 89 AB CD EF".parse().unwrap();
+        assert_eq!(block, Block([0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef]));
+        assert_eq!(u64::from(block), 0x0123456789abcdef);
+        assert_eq!(block.to_string(), "0123456789abcdef");
+        assert_eq!("0x7519".parse::<Subkey>().unwrap(), Subkey(0x7519));
+        assert_eq!("75 19".parse::<Subkey>().unwrap(), Subkey(0x7519));
+        assert!("12345".parse::<Subkey>().is_err());
+        assert_eq!(Subkey(0x0a).to_string(), "000a");
+        assert_eq!("0123456789abcdef".parse::<Key>().unwrap(), Key::N(block.0));
+        let key: Key = "0123456789abcdef0123456789abcdef".parse().unwrap();
+        assert_eq!(key.to_string(), "0123456789abcdef0123456789abcdef");
+        for bad in ["0123", "0123456789abcde", "0123456789abcdeg", "0x0x0123456789abcdef"] {
+            assert!(bad.parse::<Key>().is_err(), "{}", bad);
+            assert!(bad.parse::<Block>().is_err(), "{}", bad);
+        }
+        assert_eq!("0X0123456789ABCDEF".parse::<Block>().unwrap(), block);
+        assert_eq!(parse_hex_u64("0x8080000080800000"), Ok(0x8080000080800000));
+        assert_eq!(parse_hex_u64("0"), Ok(0));
+        assert_eq!(parse_hex_u64(""), Err(ParseHexError::Length {digits: 0, expected: "1 to 16"}));
+        assert_eq!(parse_hex_u64("0x0x1"), Err(ParseHexError::NotHex));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn hex_bytes() {
+        assert_eq!(parse_hex_bytes("68 65 6c 6c 6f").unwrap(), b"hello");
+        assert_eq!(parse_hex_bytes("0x68656C6C6F").unwrap(), b"hello");
+        assert_eq!(parse_hex_bytes("").unwrap(), b"");
+        assert_eq!(parse_hex_bytes("686"), Err(ParseHexError::Length {digits: 3, expected: "an even number of"}));
+        // Not a panic on a char boundary.
+        assert_eq!(parse_hex_bytes("aé1"), Err(ParseHexError::NotHex));
+    }
+
+    #[test]
+    fn ct_eq_and_redaction() {
+        assert!(ct_eq(&[1u8, 2, 3], &[1, 2, 3]));
+        assert!(!ct_eq(&[1u8, 2, 3], &[1, 2, 4]));
+        assert!(!ct_eq(&[1u16, 2], &[1, 2, 3]));
+        let key: Key = "0123456789abcdef".parse().unwrap();
+        assert_eq!(key, Key::N(0x0123456789abcdef_u64.to_be_bytes()));
+        assert_ne!(key, Key::NX([0x01; 16]));
+        assert_eq!(format!("{:?}", key), "Key::N(<redacted>)");
+        assert_eq!(format!("{:?}", Subkey(0x7519)), "Subkey(<redacted>)");
+        let subkeys = Subkeys::new(0x01234567, 0x89abcdef);
+        assert_eq!(*subkeys, SCHEDULE);
+        assert!(!format!("{:?}", subkeys).contains("df3b"));
+        assert_eq!(encrypt_secret(0x0123456789abcdef, 0), encrypt(0x0123456789abcdef, 0));
+        assert_eq!(decrypt_secret(0x0123456789abcdef, 0xceef2c86f2490752), 0);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn feal_debug_is_redacted() {
+        let feal = Feal::new(0x0123456789abcdef, 8);
+        assert_eq!(format!("{:?}", feal), "Feal { rounds: 8, .. }");
+        let subkeys = Key::N(0x0123456789abcdef_u64.to_be_bytes()).subkeys(8);
+        assert_eq!(*subkeys, SCHEDULE);
+        assert_eq!(format!("{:?}", subkeys), "Schedule(<redacted>)");
+        assert_eq!(feal, Feal::new(0x0123456789abcdef, 8));
+        assert_ne!(feal, Feal::new(0x0123456789abcdee, 8));
+    }
+
+    #[cfg(all(feature = "alloc", feature = "zeroize"))]
+    #[test]
+    fn zeroize_wipes_the_schedule() {
+        let mut feal = Feal::new(0x0123456789abcdef, 8);
+        feal.zeroize();
+        assert!(feal.encryption.is_empty() && feal.decryption.is_empty());
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    #[should_panic(expected = "even number of subkeys")]
+    fn odd_rounds() {
+        keygen_rounds(0x01234567, 0x89abcdef, 3);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn encrypt_block() {
+        // FEAL-8, and call-5's FEAL-NX example.
+        let feal = Feal::from_key(&Key::from(0x0123456789abcdef_u64.to_be_bytes()), 8);
+        let mut block = [0u8; 8];
+        feal.encrypt_block(&mut block);
+        assert_eq!(block, 0xceef2c86f2490752_u64.to_be_bytes());
+        feal.decrypt_block(&mut block);
+        assert_eq!(block, [0; 8]);
+
+        let feal = Feal::from_key(&"0123456789abcdef0123456789abcdef".parse().unwrap(), 32);
+        let mut block = [0u8; 8];
+        feal.encrypt_block(&mut block);
+        assert_eq!(Block(block), "9C 9B 54 97 3D F6 85 F8".parse().unwrap());
+    }
+}
@@ -0,0 +1,546 @@
+// vim: expandtab shiftwidth=4 tabstop=4:
+
+/* Build the FEAL computation graph directly in Rust, so that we no longer
+ * need graph/main.py + graph/graph.py (and networkx) to produce the
+ * graph-N.json files that the webapp reads.
+ *
+ * This is a translation of encrypt() in ../graph/main.py: the nodes are
+ * created in the same order, so the ids (and the layout) come out the same.
+ * Remember all the values (src, subkey, value, etc. are node _indices_ not actual _values_!
+ */
+
+use serde::{Deserialize, Serialize};
+
+use crate::feal::{f, fk32, u16tou64};
+
+// These are the colors graph/main.py uses.
+const SKYBLUE: &str = "#87ceeb";
+const DARKORANGE: &str = "#ff8c00";
+const LIGHTGREEN: &str = "#90ee90";
+const PURPLE: &str = "#800080";
+const VIOLET: &str = "#ee82ee";
+const LIGHTGRAY: &str = "#d3d3d3";
+const LIGHTCORAL: &str = "#f08080";
+const DARKBLUE: &str = "#00008b";
+
+// One unit of xpos/ypos is this many pixels.
+const GRID: f32 = 120.0;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Node {
+    pub id: u32,
+    pub color: String,
+    pub x: f32,
+    pub y: f32,
+    pub radius: f32,
+    pub size: f32,
+    pub bitsize: u32,
+    #[serde(flatten)]
+    pub compgraph: ComputationGraph,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "label")]
+pub enum ComputationGraph {
+    #[serde(rename = "plaintext")]
+    Plaintext {},
+
+    // index is the first subkey: the 16 bit keys feed the rounds, and the
+    // 64 bit ones (bitsize tells them apart) are the whitening keys
+    // k[index..index+4].
+    #[serde(rename = "key")]
+    Key {index: usize},
+
+    #[serde(rename = "copy16")]
+    Copy16 {src: usize},
+
+    #[serde(rename = "copy32")]
+    Copy32 {src: usize},
+
+    #[serde(rename = "copy64")]
+    Copy64 {src: usize},
+
+    #[serde(rename = "left")]
+    Left {src: usize},
+
+    #[serde(rename = "right")]
+    Right {src: usize},
+
+    #[serde(rename = "F")]
+    F {subkey: usize, value: usize},
+
+    #[serde(rename = "xor32")]
+    Xor32 {a: usize, b: usize},
+
+    #[serde(rename = "xor64")]
+    Xor64 {a: usize, b: usize},
+
+    #[serde(rename = "swap")]
+    Swap {left: usize, right: usize},
+
+    #[serde(rename = "ciphertext")]
+    Ciphertext {src: usize},
+
+    // The key schedule (keygen/keyround in feal.rs) is built out of these
+    // as well, plus left/right to split the master key into a and b.
+    #[serde(rename = "masterkey")]
+    MasterKey {},
+
+    // d starts out as 0.
+    #[serde(rename = "zero32")]
+    Zero32 {},
+
+    #[serde(rename = "fk")]
+    Fk {a: usize, b: usize},
+
+    // The high and low halves of fk's output, i.e. k0 and k1 of a keyround.
+    #[serde(rename = "left16")]
+    Left16 {src: usize},
+
+    #[serde(rename = "right16")]
+    Right16 {src: usize},
+
+    // Subkey k[index], as it comes out of the key schedule.
+    #[serde(rename = "subkey")]
+    Subkey {src: usize, index: usize},
+}
+
+impl ComputationGraph {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ComputationGraph::Plaintext {} => "plaintext",
+            ComputationGraph::Key {..} => "key",
+            ComputationGraph::Copy16 {..} => "copy16",
+            ComputationGraph::Copy32 {..} => "copy32",
+            ComputationGraph::Copy64 {..} => "copy64",
+            ComputationGraph::Left {..} => "left",
+            ComputationGraph::Right {..} => "right",
+            ComputationGraph::F {..} => "F",
+            ComputationGraph::Xor32 {..} => "xor32",
+            ComputationGraph::Xor64 {..} => "xor64",
+            ComputationGraph::Swap {..} => "swap",
+            ComputationGraph::Ciphertext {..} => "ciphertext",
+            ComputationGraph::MasterKey {} => "masterkey",
+            ComputationGraph::Zero32 {} => "zero32",
+            ComputationGraph::Fk {..} => "fk",
+            ComputationGraph::Left16 {..} => "left16",
+            ComputationGraph::Right16 {..} => "right16",
+            ComputationGraph::Subkey {..} => "subkey",
+        }
+    }
+
+    // The (name, node index) pairs of the incoming edges.
+    pub fn inputs(&self) -> Vec<(&'static str, usize)> {
+        match *self {
+            ComputationGraph::Plaintext {} => vec![],
+            ComputationGraph::Key {..} => vec![],
+            ComputationGraph::Copy16 {src} => vec![("src", src)],
+            ComputationGraph::Copy32 {src} => vec![("src", src)],
+            ComputationGraph::Copy64 {src} => vec![("src", src)],
+            ComputationGraph::Left {src} => vec![("src", src)],
+            ComputationGraph::Right {src} => vec![("src", src)],
+            ComputationGraph::F {subkey, value} => vec![("subkey", subkey), ("value", value)],
+            ComputationGraph::Xor32 {a, b} => vec![("a", a), ("b", b)],
+            ComputationGraph::Xor64 {a, b} => vec![("a", a), ("b", b)],
+            ComputationGraph::Swap {left, right} => vec![("left", left), ("right", right)],
+            ComputationGraph::Ciphertext {src} => vec![("src", src)],
+            ComputationGraph::MasterKey {} => vec![],
+            ComputationGraph::Zero32 {} => vec![],
+            ComputationGraph::Fk {a, b} => vec![("a", a), ("b", b)],
+            ComputationGraph::Left16 {src} => vec![("src", src)],
+            ComputationGraph::Right16 {src} => vec![("src", src)],
+            ComputationGraph::Subkey {src, ..} => vec![("src", src)],
+        }
+    }
+}
+
+impl Node {
+    // The label the Python pipeline used to give the node (key0, key8_11, ...)
+    pub fn label(&self) -> String {
+        match self.compgraph {
+            ComputationGraph::Key {index} if self.bitsize == 16 => format!("key{}", index),
+            ComputationGraph::Key {index} => format!("key{}_{}", index, index + self.bitsize as usize / 16 - 1),
+            ComputationGraph::Subkey {index, ..} => format!("k{}", index),
+            _ => self.compgraph.label().to_string(),
+        }
+    }
+}
+
+pub struct GraphBuilder {
+    nodes: Vec<Node>,
+}
+
+impl Default for GraphBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GraphBuilder {
+    pub fn new() -> GraphBuilder {
+        GraphBuilder{nodes: vec![]}
+    }
+
+    fn add(&mut self, compgraph: ComputationGraph, color: &str, bitsize: u32, xpos: i32, ypos: i32) -> usize {
+        let idx = self.nodes.len();
+        // Copies are drawn as little dots.
+        let is_copy = matches!(compgraph, ComputationGraph::Copy16 {..} | ComputationGraph::Copy32 {..} | ComputationGraph::Copy64 {..});
+        let (radius, size) = if is_copy { (1.0, 0.2) } else { (20.0, 0.4) };
+        self.nodes.push(Node{
+            id: idx as u32,
+            color: color.to_string(),
+            x: GRID * xpos as f32,
+            y: GRID * ypos as f32,
+            radius,
+            size: GRID * size,
+            bitsize,
+            compgraph,
+        });
+        idx
+    }
+
+    pub fn bitsize(&self, idx: usize) -> u32 {
+        self.nodes[idx].bitsize
+    }
+
+    pub fn plaintext(&mut self, xpos: i32, ypos: i32) -> usize {
+        self.add(ComputationGraph::Plaintext {}, SKYBLUE, 64, xpos, ypos)
+    }
+
+    pub fn key(&mut self, index: usize, count: usize, xpos: i32, ypos: i32) -> usize {
+        self.add(ComputationGraph::Key {index}, DARKORANGE, 16 * count as u32, xpos, ypos)
+    }
+
+    pub fn copy(&mut self, src: usize, xpos: i32, ypos: i32) -> usize {
+        let bitsize = self.bitsize(src);
+        let compgraph = match bitsize {
+            16 => ComputationGraph::Copy16 {src},
+            32 => ComputationGraph::Copy32 {src},
+            64 => ComputationGraph::Copy64 {src},
+            _ => panic!("There's no copy node for {} bits", bitsize),
+        };
+        self.add(compgraph, LIGHTGRAY, bitsize, xpos, ypos)
+    }
+
+    pub fn left(&mut self, src: usize, xpos: i32, ypos: i32) -> usize {
+        assert_eq!(self.bitsize(src), 64);
+        self.add(ComputationGraph::Left {src}, PURPLE, 32, xpos, ypos)
+    }
+
+    pub fn right(&mut self, src: usize, xpos: i32, ypos: i32) -> usize {
+        assert_eq!(self.bitsize(src), 64);
+        self.add(ComputationGraph::Right {src}, VIOLET, 32, xpos, ypos)
+    }
+
+    pub fn f(&mut self, subkey: usize, value: usize, xpos: i32, ypos: i32) -> usize {
+        assert_eq!(self.bitsize(subkey), 16);
+        assert_eq!(self.bitsize(value), 32);
+        self.add(ComputationGraph::F {subkey, value}, LIGHTCORAL, 32, xpos, ypos)
+    }
+
+    pub fn xor(&mut self, a: usize, b: usize, xpos: i32, ypos: i32) -> usize {
+        let bitsize = self.bitsize(a);
+        assert_eq!(bitsize, self.bitsize(b));
+        let compgraph = match bitsize {
+            32 => ComputationGraph::Xor32 {a, b},
+            64 => ComputationGraph::Xor64 {a, b},
+            _ => panic!("There's no xor node for {} bits", bitsize),
+        };
+        self.add(compgraph, LIGHTGREEN, bitsize, xpos, ypos)
+    }
+
+    pub fn swap(&mut self, left: usize, right: usize, xpos: i32, ypos: i32) -> usize {
+        assert_eq!(self.bitsize(left), 32);
+        assert_eq!(self.bitsize(right), 32);
+        self.add(ComputationGraph::Swap {left, right}, LIGHTGRAY, 64, xpos, ypos)
+    }
+
+    pub fn ciphertext(&mut self, src: usize, xpos: i32, ypos: i32) -> usize {
+        let bitsize = self.bitsize(src);
+        self.add(ComputationGraph::Ciphertext {src}, DARKBLUE, bitsize, xpos, ypos)
+    }
+
+    pub fn master_key(&mut self, xpos: i32, ypos: i32) -> usize {
+        self.add(ComputationGraph::MasterKey {}, SKYBLUE, 64, xpos, ypos)
+    }
+
+    pub fn zero(&mut self, xpos: i32, ypos: i32) -> usize {
+        self.add(ComputationGraph::Zero32 {}, LIGHTGRAY, 32, xpos, ypos)
+    }
+
+    pub fn fk(&mut self, a: usize, b: usize, xpos: i32, ypos: i32) -> usize {
+        assert_eq!(self.bitsize(a), 32);
+        assert_eq!(self.bitsize(b), 32);
+        self.add(ComputationGraph::Fk {a, b}, LIGHTCORAL, 32, xpos, ypos)
+    }
+
+    pub fn left16(&mut self, src: usize, xpos: i32, ypos: i32) -> usize {
+        assert_eq!(self.bitsize(src), 32);
+        self.add(ComputationGraph::Left16 {src}, PURPLE, 16, xpos, ypos)
+    }
+
+    pub fn right16(&mut self, src: usize, xpos: i32, ypos: i32) -> usize {
+        assert_eq!(self.bitsize(src), 32);
+        self.add(ComputationGraph::Right16 {src}, VIOLET, 16, xpos, ypos)
+    }
+
+    pub fn subkey(&mut self, src: usize, index: usize, xpos: i32, ypos: i32) -> usize {
+        assert_eq!(self.bitsize(src), 16);
+        self.add(ComputationGraph::Subkey {src, index}, DARKORANGE, 16, xpos, ypos)
+    }
+
+    pub fn build(self) -> Vec<Node> {
+        self.nodes
+    }
+}
+
+pub fn feal_graph(rounds: usize) -> Vec<Node> {
+    // "Applied Cryptography" Bruce Schneier 13.4 Figure 13.3
+    // This mirrors feal4_raw in feal.rs (and encrypt() in ../graph/main.py)
+    // but for any number of rounds. Subkeys 0..rounds are used by the rounds,
+    // rounds..rounds+4 whiten the input and rounds+4..rounds+8 whiten the output.
+    let starty = 5;
+    let lasty = starty + 5 * rounds as i32;
+    let mut g = GraphBuilder::new();
+
+    let plaintext = g.plaintext(0, 0);
+    let keys: Vec<usize> = (0..rounds).map(|idx| g.key(idx, 1, 3, starty + 5 * idx as i32)).collect();
+    let key_in = g.key(rounds, 4, 3, 1);
+    let key_out = g.key(rounds + 4, 4, 3, lasty + 2);
+
+    let v1 = g.xor(plaintext, key_in, 0, 1);
+    let copy = g.copy(v1, 0, 2);
+
+    let left = g.left(copy, -1, 3);
+    let right = g.right(copy, 1, 3);
+
+    let mut left = g.copy(left, -1, 4);
+    let mut right = g.xor(left, right, 1, 4);
+
+    for (idx, key) in keys.iter().enumerate() {
+        let y = starty + 5 * idx as i32;
+        right = g.copy(right, 1, y + 1);
+        let subkey = g.copy(*key, 0, y);
+        let intermediate = g.f(subkey, right, 0, y + 1);
+        let newright = g.xor(left, intermediate, -1, y + 1);
+        let newright = g.copy(newright, -1, y + 2);
+        let newleft = g.copy(right, 1, y + 2);
+        let concatenated = g.swap(newright, newleft, 0, y + 3);
+        left = g.left(concatenated, -1, y + 4);
+        right = g.right(concatenated, 1, y + 4);
+    }
+
+    let right = g.copy(right, 1, lasty);
+    let left = g.xor(left, right, -1, lasty);
+    let combined = g.swap(left, right, 0, lasty + 1);
+    let output = g.xor(combined, key_out, 0, lasty + 2);
+    g.ciphertext(output, 0, lasty + 3);
+
+    g.build()
+}
+
+pub fn key_schedule_graph(rounds: usize) -> Vec<Node> {
+    // "Applied Cryptography" Bruce Schneier 13.4 Figure 13.5
+    // This mirrors keygen_rounds in feal.rs: (rounds + 8) / 2 keyrounds, each
+    // of which makes two subkeys and rotates (a, b, d) to (b, fk(a, b ^ d), a).
+    let mut g = GraphBuilder::new();
+
+    let master = g.master_key(0, 0);
+    let copy = g.copy(master, 0, 1);
+    let mut a = g.left(copy, -1, 2);
+    let mut b = g.right(copy, 0, 2);
+    let mut d = g.zero(1, 2);
+
+    for idx in 0..(rounds + 8) / 2 {
+        let y = 3 + 4 * idx as i32;
+        let a_copy = g.copy(a, -1, y);
+        let b_copy = g.copy(b, 0, y);
+        let v = g.xor(b_copy, d, 1, y);
+        let k01 = g.fk(a_copy, v, 0, y + 1);
+        let k01_copy = g.copy(k01, 0, y + 2);
+        let k0 = g.left16(k01_copy, 2, y + 2);
+        let k1 = g.right16(k01_copy, 3, y + 2);
+        g.subkey(k0, 2 * idx, 2, y + 3);
+        g.subkey(k1, 2 * idx + 1, 3, y + 3);
+        (a, b, d) = (b_copy, k01_copy, a_copy);
+    }
+
+    g.build()
+}
+
+pub fn to_json(nodes: &[Node]) -> String {
+    // Sorted keys and four space indentation, the same as graph/graph.py wrote it.
+    let value = serde_json::to_value(nodes).unwrap();
+    let mut buffer = vec![];
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+    let mut serializer = serde_json::Serializer::with_formatter(&mut buffer, formatter);
+    value.serialize(&mut serializer).unwrap();
+    String::from_utf8(buffer).unwrap()
+}
+
+// The number of rounds is the number of F boxes, which tells us how many
+// subkeys (rounds + 8) the graph wants.
+pub fn rounds(nodes: &[Node]) -> usize {
+    nodes.iter().filter(|node| matches!(node.compgraph, ComputationGraph::F {..})).count()
+}
+
+// What eval takes for granted about a graph that came from somewhere else (a
+// JSON file): ids are positions, every input is an earlier node, and every key
+// is within the rounds + 8 subkeys. The webapp's validate.rs checks the same
+// things, and the bitsizes too. Says what the first problem is.
+// A graph that reads subkeys also needs an even, nonzero number of F nodes,
+// since that's the N its key schedule is made for. Key schedule graphs have neither.
+pub fn validate(nodes: &[Node]) -> Result<(), String> {
+    if nodes.is_empty() {
+        return Err("the graph has no nodes".to_string());
+    }
+    let keyed = nodes.iter().any(|node| matches!(node.compgraph, ComputationGraph::Key {..}));
+    if keyed && (rounds(nodes) == 0 || !rounds(nodes).is_multiple_of(2)) {
+        return Err(format!("the graph has {} F nodes, but FEAL-N needs an even, nonzero number of rounds", rounds(nodes)));
+    }
+    let subkeys = rounds(nodes) + 8;
+    for (position, node) in nodes.iter().enumerate() {
+        if node.id as usize != position {
+            return Err(format!("node {} has id {} (ids must match their position)", position, node.id));
+        }
+        for (input, src) in node.compgraph.inputs() {
+            if src >= nodes.len() {
+                return Err(format!("node {}: {}={} is out of range (there are {} nodes)", position, input, src, nodes.len()));
+            }
+            if src >= position {
+                return Err(format!("node {}: {}={} is not an earlier node (the graph must be topologically ordered)", position, input, src));
+            }
+        }
+        if let ComputationGraph::Key {index} = node.compgraph {
+            let count = if node.bitsize == 16 { 1 } else { 4 };
+            if index + count > subkeys {
+                return Err(format!("node {}: key{} is past the {} subkeys of FEAL-{}", position, index, subkeys, subkeys - 8));
+            }
+        }
+    }
+    Ok(())
+}
+
+// Evaluate the graph for a pair of plaintexts, plaintext and plaintext ^ differential,
+// under the same subkeys. This is what the webapp's inspector::evaluate does,
+// but without yew. Returns the (value1, value2) pair of every node.
+// A key schedule graph has no plaintext, so there the differential goes on
+// the master key instead (key and key ^ differential).
+pub fn eval(nodes: &[Node], key: u64, subkeys: &[u16], plaintext: u64, differential: u64) -> Vec<(u64, u64)> {
+    let mut values: Vec<(u64, u64)> = Vec::with_capacity(nodes.len());
+    for node in nodes {
+        let value = match node.compgraph {
+            ComputationGraph::Plaintext {} => (plaintext, plaintext ^ differential),
+            ComputationGraph::Key {index} => {
+                let key = if node.bitsize == 16 {
+                    subkeys[index] as u64
+                }
+                else {
+                    u16tou64(subkeys[index], subkeys[index + 1], subkeys[index + 2], subkeys[index + 3])
+                };
+                (key, key)
+            },
+            ComputationGraph::Copy16 {src} => (values[src].0 & 0x00ffff, values[src].1 & 0x00ffff),
+            ComputationGraph::Copy32 {src} => (values[src].0 & 0x00ffffffff, values[src].1 & 0x00ffffffff),
+            ComputationGraph::Copy64 {src} => values[src],
+            ComputationGraph::Left {src} => ((values[src].0 >> 32) & 0x00ffffffff, (values[src].1 >> 32) & 0x00ffffffff),
+            ComputationGraph::Right {src} => (values[src].0 & 0x00ffffffff, values[src].1 & 0x00ffffffff),
+            ComputationGraph::F {subkey, value} => {
+                let output1 = f(values[subkey].0 as u16, values[value].0 as u32);
+                let output2 = f(values[subkey].1 as u16, values[value].1 as u32);
+                (output1.into(), output2.into())
+            },
+            ComputationGraph::Xor32 {a, b} => ((values[a].0 ^ values[b].0) & 0x00ffffffff, (values[a].1 ^ values[b].1) & 0x00ffffffff),
+            ComputationGraph::Xor64 {a, b} => (values[a].0 ^ values[b].0, values[a].1 ^ values[b].1),
+            ComputationGraph::Swap {left, right} => {
+                let (left1, left2) = (values[left].0 & 0x00ffffffff, values[left].1 & 0x00ffffffff);
+                let (right1, right2) = (values[right].0 & 0x00ffffffff, values[right].1 & 0x00ffffffff);
+                (((right1 << 32) | left1), ((right2 << 32) | left2))
+            },
+            ComputationGraph::Ciphertext {src} => values[src],
+            ComputationGraph::MasterKey {} => (key, key ^ differential),
+            ComputationGraph::Zero32 {} => (0, 0),
+            ComputationGraph::Fk {a, b} => {
+                let output1 = fk32(values[a].0 as u32, values[b].0 as u32);
+                let output2 = fk32(values[a].1 as u32, values[b].1 as u32);
+                (output1.into(), output2.into())
+            },
+            ComputationGraph::Left16 {src} => ((values[src].0 >> 16) & 0x00ffff, (values[src].1 >> 16) & 0x00ffff),
+            ComputationGraph::Right16 {src} => (values[src].0 & 0x00ffff, values[src].1 & 0x00ffff),
+            ComputationGraph::Subkey {src, ..} => values[src],
+        };
+        values.push(value);
+    }
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::feal::{fealn_raw, Key};
+
+    // The committed graphs are what `graph --rounds N` writes.
+    #[test]
+    fn matches_the_static_json() {
+        assert_eq!(to_json(&feal_graph(4)) + "\n", include_str!("../webapp/static/graph-4.json"));
+        assert_eq!(to_json(&feal_graph(8)) + "\n", include_str!("../webapp/static/graph-8.json"));
+        assert_eq!(to_json(&key_schedule_graph(4)) + "\n", include_str!("../webapp/static/keyschedule-4.json"));
+    }
+
+    #[test]
+    fn eval_matches_the_cipher() {
+        let key = 0x0123456789abcdef;
+        let (plaintext, differential) = (0x5a5a5a5a5a5a5a5a, 0x8080000080800000);
+        for rounds in [4, 8, 32] {
+            let subkeys = Key::N(u64::to_be_bytes(key)).subkeys(rounds);
+            let nodes = feal_graph(rounds);
+            let values = eval(&nodes, key, &subkeys, plaintext, differential);
+            let ciphertext = nodes.iter().position(|node| matches!(node.compgraph, ComputationGraph::Ciphertext {..})).unwrap();
+            assert_eq!(values[ciphertext], (fealn_raw(&subkeys, rounds, plaintext), fealn_raw(&subkeys, rounds, plaintext ^ differential)), "FEAL-{}", rounds);
+
+            // And the key schedule graph comes up with the same subkeys.
+            let nodes = key_schedule_graph(rounds);
+            let values = eval(&nodes, key, &[], 0, 0);
+            for (node, value) in nodes.iter().zip(values.iter()) {
+                if let ComputationGraph::Subkey {index, ..} = node.compgraph {
+                    assert_eq!(value.0, subkeys[index] as u64, "FEAL-{} k{}", rounds, index);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn validate_catches_bad_inputs() {
+        assert!(validate(&feal_graph(8)).is_ok());
+        assert!(validate(&key_schedule_graph(8)).is_ok());
+        assert!(validate(&[]).is_err());
+
+        // The first xor64 is plaintext ^ key4-7.
+        let xor = feal_graph(4).iter().position(|node| matches!(node.compgraph, ComputationGraph::Xor64 {..})).unwrap();
+        for (a, problem) in [(1000, "out of range"), (xor, "not an earlier node")] {
+            let mut nodes = feal_graph(4);
+            nodes[xor].compgraph = ComputationGraph::Xor64 {a, b: 0};
+            assert!(validate(&nodes).unwrap_err().contains(problem));
+        }
+        let mut nodes = feal_graph(4);
+        nodes[1].compgraph = ComputationGraph::Key {index: 12};
+        assert!(validate(&nodes).unwrap_err().contains("past the 12 subkeys"));
+        nodes[1].id = 2;
+        assert!(validate(&nodes).unwrap_err().contains("ids must match"));
+
+        // One F, then none at all.
+        let mut g = GraphBuilder::new();
+        let plaintext = g.plaintext(0, 0);
+        let key = g.key(0, 1, 3, 1);
+        let right = g.right(plaintext, 1, 1);
+        g.f(key, right, 0, 2);
+        assert!(validate(&g.build()).unwrap_err().contains("has 1 F nodes"));
+        let mut g = GraphBuilder::new();
+        let plaintext = g.plaintext(0, 0);
+        let key = g.key(0, 4, 3, 1);
+        let xor = g.xor(plaintext, key, 0, 1);
+        g.ciphertext(xor, 0, 2);
+        assert!(validate(&g.build()).unwrap_err().contains("has 0 F nodes"));
+    }
+}
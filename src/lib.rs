@@ -0,0 +1,42 @@
+// vim: expandtab shiftwidth=4 tabstop=4:
+
+/* The FEAL code itself, so that main.rs, the tests and the benchmarks
+ * can all get at it.
+ *
+ * feal.rs is the cipher and needs nothing but core (and alloc, for the
+ * Vec based parts, with the "alloc" feature). mac.rs needs alloc too.
+ * Everything else here wants
+ * std, so it's all behind the "std" feature, which is on by default:
+ *     cargo build --lib --no-default-features --target thumbv7em-none-eabihf
+ * gets you just the cipher, for a board with no allocator.
+ */
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+#[cfg(feature = "std")]
+pub mod attack;
+#[cfg(feature = "std")]
+pub mod bitslice;
+#[cfg(feature = "std")]
+pub mod explain;
+#[cfg(feature = "std")]
+pub mod export;
+pub mod feal;
+#[cfg(feature = "std")]
+pub mod graph;
+#[cfg(feature = "alloc")]
+pub mod mac;
+#[cfg(feature = "std")]
+pub mod simd;
+#[cfg(feature = "std")]
+pub mod svg;
+#[cfg(feature = "std")]
+pub mod trace;
+
+#[cfg(feature = "std")]
+pub fn hexstr(value: u64, bitsize: u32) -> String {
+    let hex_str = format!("{:x}", value);
+    let required_chars = bitsize / 4;
+    let padded_str = format!("{:0>width$}", hex_str, width = required_chars as usize);
+    format!("0x{}", padded_str)
+}
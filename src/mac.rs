@@ -0,0 +1,234 @@
+// vim: expandtab shiftwidth=4 tabstop=4:
+
+/* Message authentication with FEAL as the block cipher: raw CBC-MAC,
+ * CMAC (a.k.a. OMAC1, NIST SP 800-38B, with the 64 bit block constant),
+ * and the classic ways of forging raw CBC-MAC tags, for the attack
+ * exercises:
+ *     cargo run -- mac --key 0123456789abcdef --message "hello"
+ *     cargo run -- verify --key 0123456789abcdef --message "hello" --tag ...
+ *     cargo run -- forge
+ *
+ * Raw CBC-MAC is only safe when every message is the same number of
+ * blocks and the key is used for nothing else. splice() is what goes
+ * wrong when the lengths vary: the tag is the whole chaining state, so
+ * a tag for one message lets you carry on from where it left off. And
+ * cbc_encrypt() is what goes wrong when the key is shared: CBC
+ * encryption under the same key with a zero IV ends with the tag. CMAC
+ * gets rid of the first by xoring one of two key-derived values into
+ * the last block, so a tag isn't a chaining state anyone can continue.
+ */
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+
+use crate::feal::{ct_eq, Block, Feal, ParseHexError};
+
+pub const BLOCK: usize = 8;
+// What doubling in GF(2^64) xors in when the top bit falls off: x^64 = x^4 + x^3 + x + 1.
+pub const RB: u64 = 0x1b;
+
+// A MAC tag. Its == is constant time, so checking one doesn't say how much of it was right.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Tag(pub [u8; BLOCK]);
+
+impl PartialEq for Tag {
+    fn eq(&self, other: &Tag) -> bool {
+        ct_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Tag {}
+
+impl FromStr for Tag {
+    type Err = ParseHexError;
+
+    fn from_str(text: &str) -> Result<Tag, ParseHexError> {
+        text.parse::<Block>().map(|block| Tag(block.0))
+    }
+}
+
+impl fmt::Display for Tag {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        Block(self.0).fmt(formatter)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mac {
+    CbcMac,
+    Cmac,
+}
+
+impl Mac {
+    pub fn tag(&self, feal: &Feal, message: &[u8]) -> Tag {
+        match self {
+            Mac::CbcMac => cbc_mac(feal, message),
+            Mac::Cmac => cmac(feal, message),
+        }
+    }
+}
+
+fn xor(state: &mut [u8; BLOCK], bytes: &[u8]) {
+    for (state, byte) in state.iter_mut().zip(bytes.iter()) {
+        *state ^= byte;
+    }
+}
+
+// The message with zeros on the end to make whole blocks (and no message is one block of them).
+fn zero_padded(message: &[u8]) -> Vec<u8> {
+    let mut padded = message.to_vec();
+    padded.resize(message.len().div_ceil(BLOCK).max(1) * BLOCK, 0);
+    padded
+}
+
+// Raw CBC-MAC: CBC with a zero IV, keeping only the last block. The message is
+// zero padded, so m and m || 00 have the same tag, which is the least of its problems.
+pub fn cbc_mac(feal: &Feal, message: &[u8]) -> Tag {
+    let mut state = [0u8; BLOCK];
+    for block in zero_padded(message).chunks(BLOCK) {
+        xor(&mut state, block);
+        feal.encrypt_block(&mut state);
+    }
+    Tag(state)
+}
+
+// Multiply by x in GF(2^64), the way CMAC derives its subkeys.
+pub fn double(value: u64) -> u64 {
+    (value << 1) ^ if value >> 63 == 1 { RB } else { 0 }
+}
+
+// CMAC's K1 and K2: L = E(0), K1 = 2L, K2 = 4L.
+pub fn cmac_subkeys(feal: &Feal) -> (u64, u64) {
+    let k1 = double(feal.encrypt(0));
+    (k1, double(k1))
+}
+
+// CMAC: CBC-MAC, except that the last block gets K1 xored in if it's whole, or
+// 10...0 padding and K2 if it isn't (or if there's no message at all).
+pub fn cmac(feal: &Feal, message: &[u8]) -> Tag {
+    let (k1, k2) = cmac_subkeys(feal);
+    let (head, last) = message.split_at(message.len().saturating_sub(1) / BLOCK * BLOCK);
+    let mut state = [0u8; BLOCK];
+    for block in head.chunks(BLOCK) {
+        xor(&mut state, block);
+        feal.encrypt_block(&mut state);
+    }
+    let mut block = [0u8; BLOCK];
+    block[..last.len()].copy_from_slice(last);
+    let subkey = if last.len() == BLOCK {
+        k1
+    }
+    else {
+        block[last.len()] = 0x80;
+        k2
+    };
+    xor(&mut block, &subkey.to_be_bytes());
+    xor(&mut state, &block);
+    feal.encrypt_block(&mut state);
+    Tag(state)
+}
+
+pub fn verify(mac: Mac, feal: &Feal, message: &[u8], tag: &Tag) -> bool {
+    mac.tag(feal, message) == *tag
+}
+
+// The raw CBC-MAC forgery: given m1 with tag t1, and m2, both under the same key,
+// m1 || (the first block of m2 ^ t1) || the rest of m2 has m2's tag, since after
+// (zero padded) m1 the chaining state is t1, which the spliced block cancels out.
+// With m2 = m1 that's a new message with a tag the attacker already has.
+pub fn splice(m1: &[u8], t1: &Tag, m2: &[u8]) -> Vec<u8> {
+    let mut forged = zero_padded(m1);
+    let mut first = [0u8; BLOCK];
+    first[..m2.len().min(BLOCK)].copy_from_slice(&m2[..m2.len().min(BLOCK)]);
+    xor(&mut first, &t1.0);
+    forged.extend_from_slice(&first);
+    forged.extend_from_slice(m2.get(BLOCK..).unwrap_or(&[]));
+    forged
+}
+
+// CBC encryption of the zero padded message. With the MAC's key and a zero IV,
+// the last block of this is the message's raw CBC-MAC: an encryption oracle
+// hands out tags.
+pub fn cbc_encrypt(feal: &Feal, iv: [u8; BLOCK], message: &[u8]) -> Vec<u8> {
+    let mut state = iv;
+    let mut ciphertext = Vec::with_capacity(message.len() + BLOCK);
+    for block in zero_padded(message).chunks(BLOCK) {
+        xor(&mut state, block);
+        feal.encrypt_block(&mut state);
+        ciphertext.extend_from_slice(&state);
+    }
+    ciphertext
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feal() -> Feal {
+        Feal::new(0x0123456789abcdef, 8)
+    }
+
+    #[test]
+    fn doubling() {
+        assert_eq!(double(1), 2);
+        assert_eq!(double(1 << 63), RB);
+        assert_eq!(double(0xc000000000000001), 0x8000000000000019);
+        let (k1, k2) = cmac_subkeys(&feal());
+        assert_eq!(k2, double(k1));
+    }
+
+    #[test]
+    fn cmac_pads_properly() {
+        let feal = feal();
+        // Every length gets its own tag, unlike raw CBC-MAC's zero padding.
+        let tags: Vec<Tag> = (0..=17).map(|len| cmac(&feal, &vec![0; len])).collect();
+        for (idx, tag) in tags.iter().enumerate() {
+            assert!(tags[idx + 1..].iter().all(|other| other != tag), "length {}", idx);
+        }
+        assert_eq!(cbc_mac(&feal, b"abc"), cbc_mac(&feal, b"abc\0\0"));
+        // One whole block: E(m ^ K1).
+        let (k1, _) = cmac_subkeys(&feal);
+        assert_eq!(cmac(&feal, b"12345678").0, feal.encrypt(u64::from_be_bytes(*b"12345678") ^ k1).to_be_bytes());
+    }
+
+    #[test]
+    fn verify_checks_every_bit() {
+        let feal = feal();
+        for mac in [Mac::CbcMac, Mac::Cmac] {
+            let tag = mac.tag(&feal, b"attack at dawn");
+            assert!(verify(mac, &feal, b"attack at dawn", &tag));
+            assert!(!verify(mac, &feal, b"attack at dusk", &tag));
+            for bit in 0..64 {
+                let mut flipped = tag;
+                flipped.0[bit / 8] ^= 1 << (bit % 8);
+                assert!(!verify(mac, &feal, b"attack at dawn", &flipped));
+            }
+        }
+    }
+
+    #[test]
+    fn splice_forges_cbc_mac_but_not_cmac() {
+        let feal = feal();
+        let (m1, m2) = (b"pay mallory $1".as_slice(), b"pay alice $10, from bob".as_slice());
+        let forged = splice(m1, &cbc_mac(&feal, m1), m2);
+        assert!(verify(Mac::CbcMac, &feal, &forged, &cbc_mac(&feal, m2)));
+        // Extending a message by itself.
+        let extended = splice(m1, &cbc_mac(&feal, m1), m1);
+        assert!(verify(Mac::CbcMac, &feal, &extended, &cbc_mac(&feal, m1)));
+
+        let forged = splice(m1, &cmac(&feal, m1), m2);
+        assert!(!verify(Mac::Cmac, &feal, &forged, &cmac(&feal, m2)));
+    }
+
+    #[test]
+    fn encryption_under_the_mac_key_leaks_tags() {
+        let feal = feal();
+        let message = b"the key is also used for encryption";
+        let ciphertext = cbc_encrypt(&feal, [0; BLOCK], message);
+        let tag = Tag(ciphertext[ciphertext.len() - BLOCK..].try_into().unwrap());
+        assert!(verify(Mac::CbcMac, &feal, message, &tag));
+    }
+}
@@ -7,14 +7,12 @@
  */
 
 // use rand::Rng;
-pub mod feal;
+use std::fs;
+use std::time::Instant;
 
-fn hexstr(value: u64, bitsize: u32) -> String {
-    let hex_str = format!("{:x}", value);
-    let required_chars = bitsize / 4;
-    let padded_str = format!("{:0>width$}", hex_str, width = required_chars as usize);
-    format!("0x{}", padded_str)
-}
+use clap::{Arg, ArgAction, ArgMatches, Command, value_parser};
+
+use feal4::{attack, explain, export, feal, graph, hexstr, mac, svg, trace};
 
 /*
 fn main1() {
@@ -133,6 +131,334 @@ fn main6() {
     println!("  {}", hexstr(differential.into(), 32));
 }
 
+fn build_graph(rounds: usize, schedule: bool) -> Vec<graph::Node> {
+    if schedule { graph::key_schedule_graph(rounds) } else { graph::feal_graph(rounds) }
+}
+
+fn write_graph(rounds: usize, schedule: bool, output: Option<&String>) {
+    // This replaces graph/main.py + graph/graph.py:
+    //     cargo run -- graph --rounds 8 --output webapp/static/graph-8.json
+    // and the key schedule the webapp shows next to it:
+    //     cargo run -- graph --rounds 8 --schedule --output webapp/static/keyschedule-8.json
+    let json = graph::to_json(&build_graph(rounds, schedule));
+    match output {
+        Some(fname) => fs::write(fname, json + "\n").expect("Unable to write graph"),
+        None => println!("{}", json),
+    }
+}
+
+fn parse_hex(value: &str) -> Result<u64, String> {
+    feal::parse_hex_u64(value).map_err(|err| format!("{}: {}", value, err))
+}
+
+// FEAL-N's rounds go in pairs (the key schedule makes two subkeys at a time),
+// so an odd N would leave the last whitening subkey at 0.
+fn parse_rounds(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(rounds) if rounds > 0 && rounds.is_multiple_of(2) => Ok(rounds),
+        Ok(rounds) => Err(format!("FEAL-N needs an even, nonzero number of rounds, not {}", rounds)),
+        Err(err) => Err(format!("{} is not a number of rounds: {}", value, err)),
+    }
+}
+
+fn parse_pairs(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(pairs) if pairs >= attack::MIN_PAIRS => Ok(pairs),
+        Ok(pairs) => Err(format!("the attack needs at least {} pairs, not {}", attack::MIN_PAIRS, pairs)),
+        Err(err) => Err(format!("{} is not a number of pairs: {}", value, err)),
+    }
+}
+
+fn load_graph(sub: &ArgMatches) -> Vec<graph::Node> {
+    match sub.get_one::<String>("input") {
+        Some(fname) => {
+            let json = fs::read_to_string(fname).expect("Unable to read graph");
+            let nodes: Vec<graph::Node> = match serde_json::from_str(&json) {
+                Ok(nodes) => nodes,
+                Err(err) => {
+                    eprintln!("{}: {}", fname, err);
+                    std::process::exit(1);
+                },
+            };
+            if let Err(err) = graph::validate(&nodes) {
+                eprintln!("{}: {}", fname, err);
+                std::process::exit(1);
+            }
+            nodes
+        },
+        None => build_graph(*sub.get_one::<usize>("rounds").unwrap(), sub.get_flag("schedule")),
+    }
+}
+
+// The (value1, value2) of every node for the key/plaintext/differential on the command line.
+// For a key schedule graph the differential is a key difference.
+fn eval_graph(nodes: &[graph::Node], sub: &ArgMatches) -> Vec<(u64, u64)> {
+    let key = *sub.get_one::<u64>("key").unwrap();
+    let subkeys = feal::Key::N(key.to_be_bytes()).subkeys(graph::rounds(nodes));
+    graph::eval(nodes, key, &subkeys, *sub.get_one::<u64>("plaintext").unwrap(), *sub.get_one::<u64>("differential").unwrap())
+}
+
+fn write_export(sub: &ArgMatches) {
+    let nodes = load_graph(sub);
+    let values = if sub.get_flag("annotate") { Some(eval_graph(&nodes, sub)) } else { None };
+    let text = match sub.get_one::<String>("format").unwrap().as_str() {
+        "dot" => export::to_dot(&nodes, values.as_deref()),
+        "graphml" => export::to_graphml(&nodes, values.as_deref()),
+        "mermaid" => export::to_mermaid(&nodes, values.as_deref()),
+        format => panic!("Unknown format {}", format),
+    };
+    match sub.get_one::<String>("output") {
+        Some(fname) => fs::write(fname, text + "\n").expect("Unable to write export"),
+        None => println!("{}", text),
+    }
+}
+
+fn write_svg(sub: &ArgMatches) {
+    // The same picture as the webapp draws, e.g. for FEAL-4 with a differential:
+    //     cargo run -- svg --rounds 4 --differential 8080000080800000 -o feal4.svg
+    let nodes = load_graph(sub);
+    let values = eval_graph(&nodes, sub);
+    let text = svg::render(&nodes, &values);
+    match sub.get_one::<String>("output") {
+        Some(fname) => fs::write(fname, text + "\n").expect("Unable to write svg"),
+        None => println!("{}", text),
+    }
+}
+
+fn run_attack(sub: &ArgMatches) {
+    // The FEAL-4 last round attack against --key, e.g.
+    //     cargo run --release --features parallel -- attack --pairs 8
+    let schedule = feal::Feal::new(*sub.get_one::<u64>("key").unwrap(), attack::ROUNDS);
+    let start = Instant::now();
+    let pairs = attack::chosen_pairs(&schedule, *sub.get_one::<usize>("pairs").unwrap(), *sub.get_one::<u64>("seed").unwrap());
+    let inner = attack::inner_survivors(&pairs);
+    println!("{} pairs, {} of 65536 K0^K1, K2^K3 candidates left", pairs.len(), inner.len());
+    let keys = attack::last_round_keys(&pairs, &inner);
+    let expected = attack::effective_key(&schedule.encryption);
+    for key in keys.iter() {
+        println!("  K = {}{}", hexstr(*key as u64, 32), if *key == expected { " (right)" } else { "" });
+    }
+    println!("{} candidates for K in {:.2?}", keys.len(), start.elapsed());
+}
+
+// "7519,71f9,..." (or with spaces), as in call-5's list of the extended key.
+fn parse_subkeys(value: &str) -> Result<Vec<u16>, String> {
+    value.split([',', ' ']).filter(|digits| !digits.is_empty()).map(|digits| {
+        digits.parse::<feal::Subkey>().map(u16::from).map_err(|err| format!("{}: {}", digits, err))
+    }).collect()
+}
+
+fn write_trace(sub: &ArgMatches) {
+    // Every intermediate value of one encryption, e.g. call-5's worked example:
+    //     cargo run -- trace --rounds 32 --key 0123456789abcdef0123456789abcdef
+    let rounds = *sub.get_one::<usize>("rounds").unwrap();
+    let schedule = match sub.get_one::<Vec<u16>>("subkeys") {
+        Some(subkeys) if subkeys.len() != rounds + 8 => {
+            eprintln!("--subkeys: FEAL-{} needs {} subkeys, not {}", rounds, rounds + 8, subkeys.len());
+            std::process::exit(1);
+        },
+        Some(subkeys) => feal::Feal::from_subkeys(subkeys.clone(), rounds),
+        None => feal::Feal::from_key(sub.get_one::<feal::Key>("key").unwrap(), rounds),
+    };
+    let steps = trace::encrypt_traced(&schedule, *sub.get_one::<u64>("plaintext").unwrap());
+    if sub.get_flag("json") {
+        println!("{}", serde_json::to_string_pretty(&steps).expect("Unable to serialize the trace"));
+    }
+    else {
+        println!("{}", steps.pretty());
+    }
+}
+
+fn run_explain(sub: &ArgMatches) {
+    // Our FEAL-NX next to call-5's worked example (section 6), step by step:
+    //     cargo run -- explain
+    // --file reads another copy of it (a corrected one, say) instead of the built in one.
+    let text = match sub.get_one::<String>("file") {
+        Some(fname) => fs::read_to_string(fname).expect("Unable to read the document"),
+        None => explain::CALL5.to_string(),
+    };
+    let published = match explain::parse(&text) {
+        Ok(published) => published,
+        Err(err) => {
+            eprintln!("Unable to parse section 6: {}", err);
+            std::process::exit(1);
+        },
+    };
+    println!("FEAL-NX, N = {}, K = 0x{:032x}, P = {}", published.rounds(), published.key, hexstr(published.plaintext, 64));
+    println!();
+    println!("{}", explain::table(&explain::explain(&published)));
+}
+
+// "68 65 6c 6c 6f" or "68656c6c6f" into bytes.
+fn parse_bytes(value: &str) -> Result<Vec<u8>, String> {
+    feal::parse_hex_bytes(value).map_err(|err| format!("{}: {}", value, err))
+}
+
+// The MAC, the key schedule and the message from the command line.
+fn mac_input(sub: &ArgMatches) -> (mac::Mac, feal::Feal, Vec<u8>) {
+    let mode = match sub.get_one::<String>("mode").unwrap().as_str() {
+        "cbc-mac" => mac::Mac::CbcMac,
+        "cmac" => mac::Mac::Cmac,
+        mode => panic!("Unknown MAC {}", mode),
+    };
+    let schedule = feal::Feal::from_key(sub.get_one::<feal::Key>("key").unwrap(), *sub.get_one::<usize>("rounds").unwrap());
+    let message = match (sub.get_one::<String>("message"), sub.get_one::<Vec<u8>>("hex")) {
+        (_, Some(bytes)) => bytes.clone(),
+        (Some(text), None) => text.as_bytes().to_vec(),
+        (None, None) => vec![],
+    };
+    (mode, schedule, message)
+}
+
+fn run_mac(sub: &ArgMatches) {
+    //     cargo run -- mac --mode cmac --key 0123456789abcdef --message "attack at dawn"
+    let (mode, schedule, message) = mac_input(sub);
+    println!("{}", mode.tag(&schedule, &message));
+}
+
+fn run_verify(sub: &ArgMatches) {
+    let (mode, schedule, message) = mac_input(sub);
+    if mac::verify(mode, &schedule, &message, sub.get_one::<mac::Tag>("tag").unwrap()) {
+        println!("OK");
+    }
+    else {
+        println!("FAILED");
+        std::process::exit(1);
+    }
+}
+
+// Printable messages as text, anything else as hex.
+fn show(message: &[u8]) -> String {
+    match std::str::from_utf8(message) {
+        Ok(text) if text.chars().all(|c| !c.is_control()) => format!("{:?}", text),
+        _ => message.iter().map(|byte| format!("{:02x}", byte)).collect(),
+    }
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value { "yes" } else { "no" }
+}
+
+fn run_forge(sub: &ArgMatches) {
+    // The raw CBC-MAC forgeries from mac.rs, against a key the "attacker" only
+    // gets to use through tags (and, in the second one, encryptions):
+    //     cargo run -- forge --m1 "pay mallory \$1" --m2 "pay alice \$10, from bob"
+    let schedule = feal::Feal::from_key(sub.get_one::<feal::Key>("key").unwrap(), *sub.get_one::<usize>("rounds").unwrap());
+    let m1 = sub.get_one::<String>("m1").unwrap().as_bytes();
+    let m2 = sub.get_one::<String>("m2").unwrap().as_bytes();
+
+    println!("Splicing two tagged messages, FEAL-{}", schedule.rounds);
+    for mode in [mac::Mac::CbcMac, mac::Mac::Cmac] {
+        let (t1, t2) = (mode.tag(&schedule, m1), mode.tag(&schedule, m2));
+        let forged = mac::splice(m1, &t1, m2);
+        println!("  {:?}", mode);
+        println!("    {:<8} {:<48} tag {}", "m1", show(m1), t1);
+        println!("    {:<8} {:<48} tag {}", "m2", show(m2), t2);
+        println!("    {:<8} {}", "forged", show(&forged));
+        println!("    {:<8} m2's tag verifies for it: {}", "", yes_no(mac::verify(mode, &schedule, &forged, &t2)));
+    }
+
+    println!();
+    println!("Encrypting with the MAC key (CBC, zero IV)");
+    let ciphertext = mac::cbc_encrypt(&schedule, [0; mac::BLOCK], m2);
+    let tag = mac::Tag(ciphertext[ciphertext.len() - mac::BLOCK..].try_into().unwrap());
+    println!("    {:<8} {}", "message", show(m2));
+    println!("    {:<8} {}", "cipher", show(&ciphertext));
+    println!("    {:<8} the last block, {}, verifies as its CbcMac tag: {}", "", tag, yes_no(mac::verify(mac::Mac::CbcMac, &schedule, m2, &tag)));
+}
+
+// The arguments mac and verify share.
+fn mac_args(command: Command) -> Command {
+    command
+        .arg(Arg::new("mode").long("mode").default_value("cmac").value_parser(["cbc-mac", "cmac"]))
+        .arg(Arg::new("key").long("key").default_value("0123456789abcdef").value_parser(|value: &str| value.parse::<feal::Key>()).help("16 hex digits for FEAL-N, 32 for FEAL-NX"))
+        .arg(Arg::new("rounds").long("rounds").default_value("8").value_parser(parse_rounds))
+        .arg(Arg::new("message").long("message").short('m').help("The message, as text"))
+        .arg(Arg::new("hex").long("hex").conflicts_with("message").value_parser(parse_bytes).help("The message, as hex bytes"))
+}
+
+// The arguments shared by everything that evaluates a graph.
+fn graph_args(command: Command) -> Command {
+    command
+        .arg(Arg::new("rounds").long("rounds").default_value("8").value_parser(parse_rounds))
+        .arg(Arg::new("input").long("input").short('i').help("Read this graph JSON instead of building FEAL-<rounds>"))
+        .arg(Arg::new("schedule").long("schedule").action(ArgAction::SetTrue).help("Build the key schedule for FEAL-<rounds> instead"))
+        .arg(Arg::new("key").long("key").default_value("0123456789abcdef").value_parser(parse_hex))
+        .arg(Arg::new("plaintext").long("plaintext").default_value("0").value_parser(parse_hex))
+        .arg(Arg::new("differential").long("differential").default_value("0").value_parser(parse_hex))
+        .arg(Arg::new("output").long("output").short('o'))
+}
+
 fn main() {
-    main6()
+    let matches = Command::new("feal4")
+        .about("FEAL experiments")
+        .subcommand(
+            Command::new("graph")
+                .about("Write the FEAL computation graph as the JSON the webapp reads")
+                .arg(Arg::new("rounds").long("rounds").default_value("8").value_parser(parse_rounds))
+                .arg(Arg::new("schedule").long("schedule").action(ArgAction::SetTrue).help("Write the key schedule graph instead"))
+                .arg(Arg::new("output").long("output").short('o'))
+        )
+        .subcommand(
+            graph_args(Command::new("export"))
+                .about("Export the computation graph as Graphviz DOT, GraphML or Mermaid")
+                .arg(Arg::new("format").long("format").short('f').default_value("dot").value_parser(["dot", "graphml", "mermaid"]))
+                .arg(Arg::new("annotate").long("annotate").action(ArgAction::SetTrue).help("Label every node with its value pair and difference"))
+        )
+        .subcommand(
+            graph_args(Command::new("svg"))
+                .about("Render the differential graph as SVG, the same as the webapp")
+        )
+        .subcommand(
+            Command::new("attack")
+                .about("Recover the last round key of FEAL-4 from chosen plaintext pairs")
+                .arg(Arg::new("key").long("key").default_value("0123456789abcdef").value_parser(parse_hex).help("The key the pairs are encrypted under"))
+                .arg(Arg::new("pairs").long("pairs").default_value("8").value_parser(parse_pairs))
+                .arg(Arg::new("seed").long("seed").default_value("0").value_parser(value_parser!(u64)).help("Seeds the plaintexts, so runs can be repeated (in decimal)"))
+        )
+        .subcommand(
+            Command::new("trace")
+                .about("Encrypt one block and show every intermediate value")
+                .arg(Arg::new("rounds").long("rounds").default_value("8").value_parser(parse_rounds))
+                .arg(Arg::new("key").long("key").default_value("0123456789abcdef").value_parser(|value: &str| value.parse::<feal::Key>()).help("16 hex digits for FEAL-N, 32 for FEAL-NX"))
+                .arg(Arg::new("subkeys").long("subkeys").value_parser(parse_subkeys).help("Use these rounds + 8 subkeys instead of the key's"))
+                .arg(Arg::new("plaintext").long("plaintext").default_value("0").value_parser(parse_hex))
+                .arg(Arg::new("json").long("json").action(ArgAction::SetTrue).help("Write the trace as JSON"))
+        )
+        .subcommand(
+            Command::new("explain")
+                .about("Compare our intermediate values with call-5's worked FEAL-NX example")
+                .arg(Arg::new("file").long("file").help("Read the example from this copy of misc/call-5-utf8.txt"))
+        )
+        .subcommand(
+            mac_args(Command::new("mac"))
+                .about("Work out the CBC-MAC or CMAC tag of a message")
+        )
+        .subcommand(
+            mac_args(Command::new("verify"))
+                .about("Check a message's tag (exits with 1 if it's wrong)")
+                .arg(Arg::new("tag").long("tag").required(true).value_parser(|value: &str| value.parse::<mac::Tag>()))
+        )
+        .subcommand(
+            Command::new("forge")
+                .about("Forge raw CBC-MAC tags by splicing messages and by reusing the key for encryption")
+                .arg(Arg::new("key").long("key").default_value("0123456789abcdef").value_parser(|value: &str| value.parse::<feal::Key>()))
+                .arg(Arg::new("rounds").long("rounds").default_value("8").value_parser(parse_rounds))
+                .arg(Arg::new("m1").long("m1").default_value("pay mallory $1"))
+                .arg(Arg::new("m2").long("m2").default_value("pay alice $10, from bob"))
+        )
+        .get_matches();
+
+    match matches.subcommand() {
+        Some(("graph", sub)) => write_graph(*sub.get_one::<usize>("rounds").unwrap(), sub.get_flag("schedule"), sub.get_one::<String>("output")),
+        Some(("export", sub)) => write_export(sub),
+        Some(("svg", sub)) => write_svg(sub),
+        Some(("attack", sub)) => run_attack(sub),
+        Some(("trace", sub)) => write_trace(sub),
+        Some(("explain", sub)) => run_explain(sub),
+        Some(("mac", sub)) => run_mac(sub),
+        Some(("verify", sub)) => run_verify(sub),
+        Some(("forge", sub)) => run_forge(sub),
+        _ => main6(),
+    }
 }
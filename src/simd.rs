@@ -0,0 +1,260 @@
+// vim: expandtab shiftwidth=4 tabstop=4:
+
+/* encrypt_blocks and decrypt_blocks: lots of blocks under one key, a
+ * vector register's worth at a time. Each 32 bit lane holds one block's
+ * half, and f works on the whole word: the byte-wise adds of gx are the
+ * packed 8 bit adds (which don't carry from one byte into the next), and
+ * shifting the word by 8 or 16 lines up the bytes that go into each g:
+ *     t = a ^ (k << 8)       (a0, a1^b0, a2^b1, a3)
+ *     x = t ^ (t >> 8)       v1 is in byte 2, v2 in byte 0
+ *     v3 = g1(v1, v2)        in byte 2
+ *     v4 = g0(v2, v3)        in byte 1
+ *     v5 = g0(a0, v3)        in byte 3
+ *     v6 = g1(a3, v4)        in byte 0
+ * and f is those four bytes put back together.
+ *
+ * On x86_64 that's SSE2 (4 blocks, always there) or AVX2 (8 blocks, if
+ * the CPU has it). Anywhere else, and for whatever's left over at the
+ * end, it's feal::fealn_raw one block at a time.
+ */
+
+use crate::feal::{fealn_raw, u16tou64, u64tou32, u32tou64, Feal};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Backend {
+    Scalar,
+    Sse2,
+    Avx2,
+}
+
+impl Backend {
+    // The fastest one this CPU can do.
+    pub fn detect() -> Backend {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("avx2") {
+                return Backend::Avx2;
+            }
+            return Backend::Sse2;
+        }
+        #[allow(unreachable_code)]
+        Backend::Scalar
+    }
+
+    // Everything this CPU can do, slowest first.
+    pub fn available() -> Vec<Backend> {
+        let mut backends = vec![Backend::Scalar];
+        #[cfg(target_arch = "x86_64")]
+        {
+            backends.push(Backend::Sse2);
+            if is_x86_feature_detected!("avx2") {
+                backends.push(Backend::Avx2);
+            }
+        }
+        backends
+    }
+}
+
+pub fn encrypt_blocks(feal: &Feal, blocks: &mut [u64]) {
+    run(Backend::detect(), &feal.encryption, feal.rounds, blocks);
+}
+
+pub fn decrypt_blocks(feal: &Feal, blocks: &mut [u64]) {
+    run(Backend::detect(), &feal.decryption, feal.rounds, blocks);
+}
+
+// The same, with a particular backend (for testing and benchmarking them against each other).
+// Panics if the CPU can't do it.
+pub fn encrypt_blocks_with(backend: Backend, feal: &Feal, blocks: &mut [u64]) {
+    run(backend, &feal.encryption, feal.rounds, blocks);
+}
+
+pub fn decrypt_blocks_with(backend: Backend, feal: &Feal, blocks: &mut [u64]) {
+    run(backend, &feal.decryption, feal.rounds, blocks);
+}
+
+fn run(backend: Backend, k: &[u16], rounds: usize, blocks: &mut [u64]) {
+    assert!(Backend::available().contains(&backend), "{:?} isn't available on this CPU", backend);
+    let done = match backend {
+        Backend::Scalar => 0,
+        #[cfg(target_arch = "x86_64")]
+        Backend::Sse2 => x86::encrypt::<x86::Sse2>(k, rounds, blocks),
+        // Safe, since we just checked the CPU has AVX2.
+        #[cfg(target_arch = "x86_64")]
+        Backend::Avx2 => unsafe { x86::encrypt_avx2(k, rounds, blocks) },
+        #[cfg(not(target_arch = "x86_64"))]
+        _ => 0,
+    };
+    for block in blocks[done..].iter_mut() {
+        *block = fealn_raw(k, rounds, *block);
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    use super::{u16tou64, u64tou32, u32tou64};
+
+    // What encrypt needs from a vector of u32 lanes.
+    pub trait Vector: Copy {
+        const LANES: usize;
+        fn splat(value: u32) -> Self;
+        fn load(values: &[u32]) -> Self;
+        fn store(self, values: &mut [u32]);
+        fn xor(self, other: Self) -> Self;
+        fn and(self, other: Self) -> Self;
+        fn or(self, other: Self) -> Self;
+        // Byte by byte, mod 256.
+        fn add8(self, other: Self) -> Self;
+        fn shl<const N: i32>(self) -> Self;
+        fn shr<const N: i32>(self) -> Self;
+    }
+
+    // SSE2 is part of x86_64, so there's nothing to check before using it.
+    #[derive(Clone, Copy)]
+    pub struct Sse2(__m128i);
+
+    impl Vector for Sse2 {
+        const LANES: usize = 4;
+        #[inline(always)]
+        fn splat(value: u32) -> Self { unsafe { Sse2(_mm_set1_epi32(value as i32)) } }
+        #[inline(always)]
+        fn load(values: &[u32]) -> Self { unsafe { Sse2(_mm_loadu_si128(values[..4].as_ptr() as *const __m128i)) } }
+        #[inline(always)]
+        fn store(self, values: &mut [u32]) { unsafe { _mm_storeu_si128(values[..4].as_mut_ptr() as *mut __m128i, self.0) } }
+        #[inline(always)]
+        fn xor(self, other: Self) -> Self { unsafe { Sse2(_mm_xor_si128(self.0, other.0)) } }
+        #[inline(always)]
+        fn and(self, other: Self) -> Self { unsafe { Sse2(_mm_and_si128(self.0, other.0)) } }
+        #[inline(always)]
+        fn or(self, other: Self) -> Self { unsafe { Sse2(_mm_or_si128(self.0, other.0)) } }
+        #[inline(always)]
+        fn add8(self, other: Self) -> Self { unsafe { Sse2(_mm_add_epi8(self.0, other.0)) } }
+        #[inline(always)]
+        fn shl<const N: i32>(self) -> Self { unsafe { Sse2(_mm_slli_epi32::<N>(self.0)) } }
+        #[inline(always)]
+        fn shr<const N: i32>(self) -> Self { unsafe { Sse2(_mm_srli_epi32::<N>(self.0)) } }
+    }
+
+    // Only ever used inside encrypt_avx2, which makes sure the CPU has it.
+    #[derive(Clone, Copy)]
+    pub struct Avx2(__m256i);
+
+    impl Vector for Avx2 {
+        const LANES: usize = 8;
+        #[inline(always)]
+        fn splat(value: u32) -> Self { unsafe { Avx2(_mm256_set1_epi32(value as i32)) } }
+        #[inline(always)]
+        fn load(values: &[u32]) -> Self { unsafe { Avx2(_mm256_loadu_si256(values[..8].as_ptr() as *const __m256i)) } }
+        #[inline(always)]
+        fn store(self, values: &mut [u32]) { unsafe { _mm256_storeu_si256(values[..8].as_mut_ptr() as *mut __m256i, self.0) } }
+        #[inline(always)]
+        fn xor(self, other: Self) -> Self { unsafe { Avx2(_mm256_xor_si256(self.0, other.0)) } }
+        #[inline(always)]
+        fn and(self, other: Self) -> Self { unsafe { Avx2(_mm256_and_si256(self.0, other.0)) } }
+        #[inline(always)]
+        fn or(self, other: Self) -> Self { unsafe { Avx2(_mm256_or_si256(self.0, other.0)) } }
+        #[inline(always)]
+        fn add8(self, other: Self) -> Self { unsafe { Avx2(_mm256_add_epi8(self.0, other.0)) } }
+        #[inline(always)]
+        fn shl<const N: i32>(self) -> Self { unsafe { Avx2(_mm256_slli_epi32::<N>(self.0)) } }
+        #[inline(always)]
+        fn shr<const N: i32>(self) -> Self { unsafe { Avx2(_mm256_srli_epi32::<N>(self.0)) } }
+    }
+
+    // Rotate every byte left two bits.
+    #[inline(always)]
+    fn rotl2<V: Vector>(y: V) -> V {
+        y.shl::<2>().and(V::splat(0xfcfcfcfc)).or(y.shr::<6>().and(V::splat(0x03030303)))
+    }
+
+    // feal::f, with the subkey already splatted and shifted: k is subkey << 8.
+    #[inline(always)]
+    fn f<V: Vector>(k: V, a: V) -> V {
+        let t = a.xor(k);
+        let x = t.xor(t.shr::<8>());
+        let v3 = rotl2(x.add8(x.shl::<16>()).add8(V::splat(0x00010000)));
+        let v4 = rotl2(x.shl::<8>().add8(v3.shr::<8>()));
+        let v5 = rotl2(t.add8(v3.shl::<8>()));
+        let v6 = rotl2(t.add8(v4.shr::<8>()).add8(V::splat(0x00000001)));
+        v5.and(V::splat(0xff000000))
+            .or(v3.and(V::splat(0x00ff0000)))
+            .or(v4.and(V::splat(0x0000ff00)))
+            .or(v6.and(V::splat(0x000000ff)))
+    }
+
+    // Encrypt as many whole vectors' worth of blocks as there are, and say how many that was.
+    #[inline(always)]
+    pub fn encrypt<V: Vector>(k: &[u16], rounds: usize, blocks: &mut [u64]) -> usize {
+        let keys: Vec<V> = k[..rounds].iter().map(|subkey| V::splat((*subkey as u32) << 8)).collect();
+        let (inhi, inlo) = u64tou32(u16tou64(k[rounds], k[rounds + 1], k[rounds + 2], k[rounds + 3]));
+        let (outhi, outlo) = u64tou32(u16tou64(k[rounds + 4], k[rounds + 5], k[rounds + 6], k[rounds + 7]));
+        let (inhi, inlo, outhi, outlo) = (V::splat(inhi), V::splat(inlo), V::splat(outhi), V::splat(outlo));
+
+        let mut chunks = blocks.chunks_exact_mut(V::LANES);
+        let mut done = 0;
+        for chunk in &mut chunks {
+            let (mut his, mut los) = ([0u32; 8], [0u32; 8]);
+            for (idx, block) in chunk.iter().enumerate() {
+                (his[idx], los[idx]) = u64tou32(*block);
+            }
+            let mut left = V::load(&his).xor(inhi);
+            let mut right = V::load(&los).xor(inlo).xor(left);
+            for key in keys.iter() {
+                (left, right) = (right, left.xor(f(*key, right)));
+            }
+            left = left.xor(right);
+            right.xor(outhi).store(&mut his);
+            left.xor(outlo).store(&mut los);
+            for (idx, block) in chunk.iter_mut().enumerate() {
+                *block = u32tou64(his[idx], los[idx]);
+            }
+            done += V::LANES;
+        }
+        done
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn encrypt_avx2(k: &[u16], rounds: usize, blocks: &mut [u64]) -> usize {
+        encrypt::<Avx2>(k, rounds, blocks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+
+    use super::*;
+
+    #[test]
+    fn feal8_test_vector() {
+        let feal = Feal::new(0x0123456789abcdef, 8);
+        for backend in Backend::available() {
+            let mut blocks = [0u64; 11];
+            encrypt_blocks_with(backend, &feal, &mut blocks);
+            assert_eq!(blocks, [0xceef2c86f2490752; 11], "{:?}", backend);
+            decrypt_blocks_with(backend, &feal, &mut blocks);
+            assert_eq!(blocks, [0; 11], "{:?}", backend);
+        }
+    }
+
+    #[test]
+    fn backends_agree() {
+        let mut rng = StdRng::seed_from_u64(4);
+        for rounds in [4, 8, 16, 32] {
+            let feal = Feal::new(rng.gen(), rounds);
+            // Not a multiple of 8, so there's a scalar tail too.
+            let blocks: Vec<u64> = (0..301).map(|_| rng.gen()).collect();
+            let expected: Vec<u64> = blocks.iter().map(|block| feal.encrypt(*block)).collect();
+            for backend in Backend::available() {
+                let mut encrypted = blocks.clone();
+                encrypt_blocks_with(backend, &feal, &mut encrypted);
+                assert_eq!(encrypted, expected, "FEAL-{} with {:?}", rounds, backend);
+                decrypt_blocks_with(backend, &feal, &mut encrypted);
+                assert_eq!(encrypted, blocks, "FEAL-{} with {:?}", rounds, backend);
+            }
+        }
+    }
+}
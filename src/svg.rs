@@ -0,0 +1,102 @@
+// vim: expandtab shiftwidth=4 tabstop=4:
+
+/* Render the same SVG as GraphImpl::render in the webapp, but from the
+ * headless graph model, so the figures for slides and handouts come from a
+ * command instead of a browser screenshot. If you change the drawing in
+ * webapp/src/computation_graph.rs or webapp/src/graph_impl.rs, change it here too.
+ */
+
+use crate::graph::Node;
+use crate::hexstr;
+
+fn compute_size(nodes: &[Node]) -> (f32, f32, f32, f32) {
+    let min_x = nodes.iter().map(|node| node.x).min_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal)).unwrap_or_default();
+    let max_x = nodes.iter().map(|node| node.x).max_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal)).unwrap_or_default();
+    let min_y = nodes.iter().map(|node| node.y).min_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal)).unwrap_or_default();
+    let max_y = nodes.iter().map(|node| node.y).max_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal)).unwrap_or_default();
+
+    (min_x, max_x, min_y, max_y)
+}
+
+// The label the webapp shows (key8-11 rather than key8_11).
+fn display_label(node: &Node) -> String {
+    node.label().replace('_', "-")
+}
+
+fn render_node(node: &Node, (path1, path2): (u64, u64)) -> String {
+    let (realx, realy) = (node.x, node.y);
+    let cx_str = format!("{}", (realx as i32));
+    let cy_str = format!("{}", (realy as i32));
+    let y1 = realy + 36.0; // or some adjusted value for the first line
+    let y2 = realy + 46.0; // for the second line
+    let y3 = realy + 56.0; // for the third line
+    let differential = path1 ^ path2;
+    [
+        format!("<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"{}\" />", cx_str, cy_str, node.radius, node.color),
+        format!("<text x=\"{}\" y=\"{}\" font-family=\"Arial\" font-size=\"10\" fill=\"black\" text-anchor=\"middle\" dy=\".3em\">{}</text>", cx_str, cy_str, display_label(node)),
+        format!("<rect x=\"{}\" y=\"{}\" width=\"112\" height=\"50\" rx=\"10\" ry=\"10\" fill=\"white\" stroke=\"black\"/>", realx-56.0, realy+21.),
+        format!("<text x=\"{}\" y=\"{}\" font-family=\"Arial\" font-size=\"10\" fill=\"black\" text-anchor=\"middle\" dy=\".3em\">{}</text>", realx, y1, hexstr(path1, node.bitsize)),
+        format!("<text x=\"{}\" y=\"{}\" font-family=\"Arial\" font-size=\"10\" fill=\"black\" text-anchor=\"middle\" dy=\".3em\">{}</text>", realx, y2, hexstr(path2, node.bitsize)),
+        format!("<text x=\"{}\" y=\"{}\" font-family=\"Arial\" font-size=\"10\" fill=\"red\" text-anchor=\"middle\" dy=\".3em\">{}</text>", realx, y3, hexstr(differential, node.bitsize)),
+    ].join("\n")
+}
+
+fn render_edge(dst: &Node, src: &Node) -> String {
+    let markersz = 20.0;
+
+    let theta = f32::atan2(dst.y - src.y, dst.x - src.x);
+    let (srcx, srcy) = (src.x + 1.0 * src.radius * theta.cos(), src.y + 1.0 * src.radius * theta.sin());
+    let (dstx, dsty) = (dst.x - 1.0 * (dst.radius+markersz) * theta.cos(), dst.y - 1.0 * (dst.radius+markersz) * theta.sin());
+
+    let d = format!("M {},{} L {},{}", srcx, srcy, dstx, dsty);
+
+    format!("<path d=\"{}\" fill=\"none\" stroke=\"black\" stroke-width=\"2\" marker-end=\"url(#arrowhead)\" />", d)
+}
+
+// values are the (value1, value2) pairs from graph::eval
+pub fn render(nodes: &[Node], values: &[(u64, u64)]) -> String {
+    let (minx, maxx, miny, maxy) = compute_size(nodes);
+    let scale = 1.0;
+    let margin = 100.0;
+    let width = maxx - minx; let width_s = format!("{}", ((width + 2.0 * margin) as i32));
+    let height = maxy - miny; let height_s = format!("{}", ((height + 100.0 + 2.0 * margin) as i32));
+    let transform_s = format!("translate({}, {}) scale({}, {})", -minx + margin, -miny + margin, scale, scale);
+
+    let edge_svgs: Vec<String> = nodes.iter()
+        .flat_map(|node| node.compgraph.inputs().into_iter().map(move |(_, src)| (node, src)))
+        .map(|(node, src)| render_edge(node, &nodes[src]))
+        .collect();
+    let node_svgs: Vec<String> = nodes.iter().map(|node| render_node(node, values[node.id as usize])).collect();
+
+    [
+        format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">", width_s, height_s),
+        "<defs>".to_string(),
+        "<marker id=\"arrowhead\" markerWidth=\"10\" markerHeight=\"7\" refX=\"0\" refY=\"3.5\" orient=\"auto\">".to_string(),
+        "<polygon points=\"0 0, 10 3.5, 0 7\" />".to_string(),
+        "</marker>".to_string(),
+        "</defs>".to_string(),
+        format!("<g transform=\"{}\">", transform_s),
+        edge_svgs.join("\n"),
+        node_svgs.join("\n"),
+        "</g>".to_string(),
+        "</svg>".to_string(),
+    ].join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::feal::Key;
+    use crate::graph::{eval, feal_graph};
+
+    #[test]
+    fn a_circle_per_node_and_a_path_per_edge() {
+        let key = 0x0123456789abcdef;
+        let nodes = feal_graph(4);
+        let values = eval(&nodes, key, &Key::N(u64::to_be_bytes(key)).subkeys(4), 0, 0x8080000080800000);
+        let svg = render(&nodes, &values);
+        assert_eq!(svg.matches("<circle ").count(), nodes.len());
+        assert_eq!(svg.matches("<path ").count(), nodes.iter().map(|node| node.compgraph.inputs().len()).sum::<usize>());
+    }
+}
@@ -0,0 +1,167 @@
+// vim: expandtab shiftwidth=4 tabstop=4:
+
+/* Everything fealn_raw works out on the way to the ciphertext, in the
+ * order it does it, laid out like the worked example in
+ * misc/call-5-utf8.txt (section 6.4) so the two can be read side by side:
+ *     cargo run -- trace --rounds 32 --subkeys 7519,71f9,...
+ * or as JSON with --json. Values are hex strings in the JSON, since
+ * that's how everyone writes them.
+ */
+
+use serde::{Serialize, Serializer};
+
+use crate::feal::{f, u16tou64, u32tou64, u64tou32, Feal};
+
+fn hex16<S: Serializer>(value: &u16, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format!("0x{:04x}", value))
+}
+
+fn hex32<S: Serializer>(value: &u32, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format!("0x{:08x}", value))
+}
+
+fn hex64<S: Serializer>(value: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format!("0x{:016x}", value))
+}
+
+// Round i (from 1) takes (L[i-1], R[i-1]) to (L[i], R[i]) = (R[i-1], L[i-1] ^ f(R[i-1], K[i-1])).
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Round {
+    pub round: usize,
+    #[serde(serialize_with = "hex16")]
+    pub subkey: u16,
+    #[serde(serialize_with = "hex32")]
+    pub f_input: u32,
+    #[serde(serialize_with = "hex32")]
+    pub f_output: u32,
+    #[serde(serialize_with = "hex32")]
+    pub left: u32,
+    #[serde(serialize_with = "hex32")]
+    pub right: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Trace {
+    pub rounds: usize,
+    #[serde(serialize_with = "hex64")]
+    pub plaintext: u64,
+    #[serde(serialize_with = "hex64")]
+    pub input_whitening: u64,
+    // plaintext ^ input_whitening
+    #[serde(serialize_with = "hex64")]
+    pub whitened: u64,
+    // (L0, R0): whitened with the left half xored into the right.
+    #[serde(serialize_with = "hex64")]
+    pub mixed: u64,
+    pub steps: Vec<Round>,
+    // (R[n], L[n])
+    #[serde(serialize_with = "hex64")]
+    pub swapped: u64,
+    // swapped with R[n] xored into the right half.
+    #[serde(serialize_with = "hex64")]
+    pub unmixed: u64,
+    #[serde(serialize_with = "hex64")]
+    pub output_whitening: u64,
+    #[serde(serialize_with = "hex64")]
+    pub ciphertext: u64,
+}
+
+// feal.encrypt(plaintext), showing its work.
+pub fn encrypt_traced(feal: &Feal, plaintext: u64) -> Trace {
+    let (k, rounds) = (&feal.encryption, feal.rounds);
+    let input_whitening = u16tou64(k[rounds], k[rounds + 1], k[rounds + 2], k[rounds + 3]);
+    let output_whitening = u16tou64(k[rounds + 4], k[rounds + 5], k[rounds + 6], k[rounds + 7]);
+
+    let whitened = plaintext ^ input_whitening;
+    let (mut left, mut right) = u64tou32(whitened);
+    right ^= left;
+    let mixed = u32tou64(left, right);
+
+    let steps: Vec<Round> = k.iter().take(rounds).enumerate().map(|(idx, subkey)| {
+        let f_output = f(*subkey, right);
+        let f_input = right;
+        (left, right) = (right, left ^ f_output);
+        Round {round: idx + 1, subkey: *subkey, f_input, f_output, left, right}
+    }).collect();
+
+    let swapped = u32tou64(right, left);
+    let unmixed = u32tou64(right, left ^ right);
+    Trace {rounds, plaintext, input_whitening, whitened, mixed, steps, swapped, unmixed, output_whitening, ciphertext: unmixed ^ output_whitening}
+}
+
+// "19 6A 9A B1", the way call-5 writes them.
+pub(crate) fn bytes(value: u64, count: usize) -> String {
+    (0..count).rev().map(|idx| format!("{:02X}", (value >> (8 * idx)) & 0xff)).collect::<Vec<String>>().join(" ")
+}
+
+impl Trace {
+    // A table like call-5's section 6.4.
+    pub fn pretty(&self) -> String {
+        let n = self.rounds;
+        let mut lines = vec![
+            format!("{:<28} {}", "P", bytes(self.plaintext, 8)),
+            format!("{:<28} {}", format!("(K{}, K{}, K{}, K{})", n, n + 1, n + 2, n + 3), bytes(self.input_whitening, 8)),
+            format!("{:<28} {}", "(L0, R0) = P ^ K", bytes(self.whitened, 8)),
+            format!("{:<28} {}", "(L0, R0) ^= (0, L0)", bytes(self.mixed, 8)),
+            String::new(),
+            format!("{:>3}  {:<11}  {:<11}  {:<5}  {}", "i", "Li", "Ri", "Ki-1", "f(Ri-1, Ki-1)"),
+            format!("{:>3}  {}  {}", 0, bytes(self.mixed >> 32, 4), bytes(self.mixed, 4)),
+        ];
+        for step in self.steps.iter() {
+            lines.push(format!("{:>3}  {}  {}  {}  {}", step.round, bytes(step.left as u64, 4), bytes(step.right as u64, 4), bytes(step.subkey as u64, 2), bytes(step.f_output as u64, 4)));
+        }
+        lines.extend([
+            String::new(),
+            format!("{:<28} {}", format!("(R{}, L{})", n, n), bytes(self.swapped, 8)),
+            format!("{:<28} {}", format!("(R{}, L{}) ^= (0, R{})", n, n, n), bytes(self.unmixed, 8)),
+            format!("{:<28} {}", format!("(K{}, K{}, K{}, K{})", n + 4, n + 5, n + 6, n + 7), bytes(self.output_whitening, 8)),
+            format!("{:<28} {}", "C", bytes(self.ciphertext, 8)),
+        ]);
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The extended key K0..K39 and part of the round table from section 6.4 of
+    // misc/call-5-utf8.txt (FEAL-NX, N = 32, K = 0123456789ABCDEF0123456789ABCDEF).
+    const CALL5_SUBKEYS: [u16; 40] = [
+        0x7519, 0x71f9, 0x84e9, 0x4886, 0x88e5, 0x523b, 0x4ea4, 0x7ade,
+        0xfe40, 0x5e76, 0x9819, 0xeeac, 0x1bd4, 0x2455, 0xdca0, 0x653b,
+        0x3e32, 0x4652, 0x1cc1, 0x34df, 0x778b, 0x771d, 0xd324, 0x8410,
+        0x1ca8, 0xbc64, 0xa0db, 0xbdd2, 0x1f5f, 0x8f1c, 0x6b81, 0xb560,
+        0x196a, 0x9ab1, 0xe015, 0x8190, 0x9f72, 0x6643, 0xad32, 0x683a,
+    ];
+
+    #[test]
+    fn matches_call5() {
+        let trace = encrypt_traced(&Feal::from_subkeys(CALL5_SUBKEYS.to_vec(), 32), 0);
+        assert_eq!(trace.whitened, 0x196a9ab1e0158190);
+        assert_eq!(trace.mixed, 0x196a9ab1f97f1b21);
+        let first = &trace.steps[0];
+        assert_eq!((first.left, first.right, first.subkey, first.f_output), (0xf97f1b21, 0x4c3667cd, 0x7519, 0x555cfd7c));
+        let table: [(u32, u32, u32); 4] = [
+            (0x4c3667cd, 0xde025865, 0x277d4344),
+            (0x3b40e0fa, 0x83505f94, 0x591b7fe9),
+            (0x4446bce4, 0xfafe290b, 0xcd2f4abc),
+            (0x932ddf16, 0x03e932d4, 0xa5d6fd50),
+        ];
+        for (step, (left, right, f_output)) in [2, 8, 16, 32].iter().zip(table.iter()) {
+            let round = &trace.steps[step - 1];
+            assert_eq!((round.left, round.right, round.f_output), (*left, *right, *f_output), "round {}", step);
+        }
+        assert_eq!(trace.swapped, 0x03e932d4932ddf16);
+        assert_eq!(trace.unmixed, 0x03e932d490c4edc2);
+        assert_eq!(trace.ciphertext, 0x9c9b54973df685f8);
+    }
+
+    #[test]
+    fn matches_encrypt() {
+        let feal = Feal::new(0x0123456789abcdef, 8);
+        for plaintext in [0, 1, 0xdeadbeefcafef00d] {
+            assert_eq!(encrypt_traced(&feal, plaintext).ciphertext, feal.encrypt(plaintext));
+        }
+    }
+}
@@ -0,0 +1,123 @@
+// vim: expandtab shiftwidth=4 tabstop=4:
+
+/* The classic last round attack on FEAL-4, small enough to run in a browser.
+ * This file is shared with the worker (src/bin/worker.rs), so keep it free of yew.
+ * How the attack works, and the check for each candidate, is in the main
+ * crate's src/attack/candidates.rs, which we include as is; this is just the
+ * chosen pairs and the jobs the workers run.
+ */
+
+use gloo_worker::{HandlerId, Worker, WorkerScope};
+use serde::{Deserialize, Serialize};
+
+use crate::feal::fealn_raw;
+
+#[path = "../../src/attack/candidates.rs"]
+pub mod candidates;
+
+pub use candidates::{effective_key, Pair, Stage, CANDIDATES, MIN_PAIRS, PLAINTEXT_DIFFERENTIAL, ROUNDS};
+use candidates::last_round;
+
+// Encrypt count pairs of plaintexts (from next) under subkeys, which
+// the attacker doesn't get to see.
+pub fn chosen_pairs(subkeys: &[u16], count: usize, mut next: impl FnMut() -> u64) -> Vec<Pair> {
+    (0..count).map(|_| {
+        let p1 = next();
+        let p2 = p1 ^ PLAINTEXT_DIFFERENTIAL;
+        Pair {c1: fealn_raw(subkeys, ROUNDS, p1), c2: fealn_raw(subkeys, ROUNDS, p2)}
+    }).collect()
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Job {
+    pub id: u32,
+    pub stage: Stage,
+    pub pairs: Vec<Pair>,
+    // The candidates start..end.
+    pub start: u32,
+    pub end: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct JobResult {
+    pub id: u32,
+    pub stage: Stage,
+    // counts[i] is how many candidates were still standing after the first i pairs.
+    pub counts: Vec<u32>,
+    // The candidates that got through every pair (in the stage's encoding),
+    // and for the outer stage, the whole K they make.
+    pub survivors: Vec<u32>,
+    pub keys: Vec<u32>,
+}
+
+pub fn run(job: &Job) -> JobResult {
+    let pairs: Vec<(u32, u32, u32)> = job.pairs.iter().map(last_round).collect();
+    let mut counts = vec![0; pairs.len() + 1];
+    let mut survivors = vec![];
+    for candidate in job.start..job.end {
+        let passed = job.stage.passed(candidate, &pairs);
+        for count in counts.iter_mut().take(passed + 1) {
+            *count += 1;
+        }
+        if passed == pairs.len() {
+            survivors.push(candidate);
+        }
+    }
+    let keys = match job.stage {
+        Stage::Inner => vec![],
+        Stage::Outer {..} => survivors.iter().map(|candidate| job.stage.key(*candidate)).collect(),
+    };
+    JobResult {id: job.id, stage: job.stage, counts, survivors, keys}
+}
+
+// The web worker: it just runs whatever jobs it's sent.
+pub struct SearchWorker;
+
+impl Worker for SearchWorker {
+    type Message = ();
+    type Input = Job;
+    type Output = JobResult;
+
+    fn create(_scope: &WorkerScope<Self>) -> Self {
+        SearchWorker
+    }
+
+    fn update(&mut self, _scope: &WorkerScope<Self>, _msg: Self::Message) {}
+
+    fn received(&mut self, scope: &WorkerScope<Self>, msg: Self::Input, id: HandlerId) {
+        scope.respond(id, run(&msg));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::feal::keygen_rounds;
+
+    // The same search as the attack panel does, on one thread: every inner
+    // candidate, then every outer one for each inner survivor.
+    fn last_round_keys(pairs: &[Pair]) -> Vec<u32> {
+        let inner = run(&Job {id: 0, stage: Stage::Inner, pairs: pairs.to_vec(), start: 0, end: 1 << 16});
+        assert_eq!(inner.counts[0], 1 << 16);
+        inner.survivors.iter().flat_map(|candidate| {
+            let stage = Stage::Outer {inner: *candidate as u16};
+            run(&Job {id: 1, stage, pairs: pairs.to_vec(), start: 0, end: 1 << 16}).keys
+        }).collect()
+    }
+
+    // The same as the main crate's attack.rs test, but through the jobs the workers run.
+    #[test]
+    fn finds_the_last_round_key() {
+        for seed in 0..3u64 {
+            let subkeys = keygen_rounds(0x01234567 ^ seed as u32, 0x89abcdef, ROUNDS);
+            // Any plaintexts will do, as long as they're all different.
+            let mut state = seed;
+            let next = || {
+                state = state.wrapping_add(0x9e3779b97f4a7c15);
+                (state ^ (state >> 31)).wrapping_mul(0xbf58476d1ce4e5b9)
+            };
+            let pairs = chosen_pairs(&subkeys, 6, next);
+            assert!(last_round_keys(&pairs).contains(&effective_key(&subkeys)), "seed {}", seed);
+        }
+    }
+}
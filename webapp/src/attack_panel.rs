@@ -0,0 +1,296 @@
+// vim: expandtab shiftwidth=4 tabstop=4:
+
+/* Run the FEAL-4 last round attack (see attack.rs) right here: make up a
+ * hidden key, encrypt chosen plaintext pairs under it, and hand the key
+ * search to a few web workers. As their results come in, we show how
+ * many candidates are left after each pair, and when it's over, the Key
+ * nodes the answer stands for get marked on the graph.
+ */
+
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+
+use gloo_worker::{Spawnable, WorkerBridge};
+use web_sys::HtmlInputElement;
+use yew::{
+    Callback,
+    function_component,
+    html,
+    Html,
+    NodeRef,
+    Properties,
+    use_force_update,
+    use_mut_ref,
+    use_state
+};
+
+use crate::attack::{chosen_pairs, effective_key, Job, JobResult, Pair, SearchWorker, Stage, CANDIDATES, MIN_PAIRS, ROUNDS};
+use crate::feal::{keygen_rounds, u64tou32};
+use crate::graph::Node;
+use crate::graph_impl::random_u64;
+
+const WORKERS: usize = 4;
+// Each stage is 2^16 candidates, cut up into this many jobs.
+const INNER_JOBS: u32 = 64;
+const OUTER_JOBS: u32 = 16;
+// The pairs you can ask for: fewer than MIN_PAIRS leaves thousands of keys, and 64 is plenty.
+const MAX_PAIRS: usize = 64;
+
+// What the attack found, for the graph to show: which Key nodes (by index)
+// it's about, and what to write next to them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recovered {
+    pub indices: Vec<usize>,
+    pub text: String,
+}
+
+// A ring around the node and the text above it, like characteristic::render_check.
+pub fn render_recovered(node: &Node, recovered: &Recovered) -> Html {
+    html! {
+        <>
+            <circle cx={format!("{}", node.x)} cy={format!("{}", node.y)} r={format!("{}", node.radius + 8.0)} fill="none" stroke="magenta" stroke-width="4" pointer-events="none" />
+            <text x={format!("{}", node.x)} y={format!("{}", node.y - node.radius - 24.0)} font-family="Arial" font-size="12" fill="magenta" text-anchor="middle" dy=".3em">{recovered.text.clone()}</text>
+        </>
+    }
+}
+
+// Where a run is at. counts are summed over the jobs of each stage, so
+// they're only the whole story once the stage is done.
+#[derive(Default)]
+struct Search {
+    pairs: Vec<Pair>,
+    next_id: u32,
+    next_worker: usize,
+    inner_pending: u32,
+    inner_counts: Vec<u32>,
+    inner_survivors: Vec<u16>,
+    outer_pending: u32,
+    outer_counts: Vec<u32>,
+    keys: Vec<u32>,
+    started: f64,
+    finished: Option<f64>,
+}
+
+impl Search {
+    fn new(pairs: Vec<Pair>) -> Search {
+        let counts = vec![0; pairs.len() + 1];
+        Search {pairs, inner_counts: counts.clone(), outer_counts: counts, started: js_sys::Date::now(), ..Search::default()}
+    }
+
+    // The jobs splitting up one stage's 2^16 candidates.
+    fn jobs(&mut self, stage: Stage, count: u32) -> Vec<Job> {
+        let size = CANDIDATES / count;
+        (0..count).map(|job| {
+            self.next_id += 1;
+            Job {id: self.next_id, stage, pairs: self.pairs.clone(), start: job * size, end: (job + 1) * size}
+        }).collect()
+    }
+
+    fn dispatch(&mut self, bridges: &[WorkerBridge<SearchWorker>], jobs: Vec<Job>) {
+        for job in jobs {
+            bridges[self.next_worker % bridges.len()].send(job);
+            self.next_worker += 1;
+        }
+    }
+
+    fn add(counts: &mut [u32], more: &[u32]) {
+        for (count, more) in counts.iter_mut().zip(more.iter()) {
+            *count += more;
+        }
+    }
+
+    // Returns the jobs the result makes necessary (the outer stage, once the inner one is done).
+    fn receive(&mut self, result: JobResult) -> Vec<Job> {
+        match result.stage {
+            Stage::Inner => {
+                Search::add(&mut self.inner_counts, &result.counts);
+                self.inner_survivors.extend(result.survivors.iter().map(|survivor| *survivor as u16));
+                self.inner_pending -= 1;
+                if self.inner_pending > 0 {
+                    return vec![];
+                }
+                let survivors = self.inner_survivors.clone();
+                let jobs: Vec<Job> = survivors.into_iter().flat_map(|inner| self.jobs(Stage::Outer {inner}, OUTER_JOBS)).collect();
+                self.outer_pending = jobs.len() as u32;
+                if jobs.is_empty() {
+                    self.finished = Some(js_sys::Date::now());
+                }
+                jobs
+            },
+            Stage::Outer {..} => {
+                Search::add(&mut self.outer_counts, &result.counts);
+                self.keys.extend(result.keys);
+                self.outer_pending -= 1;
+                if self.outer_pending == 0 {
+                    self.keys.sort();
+                    self.finished = Some(js_sys::Date::now());
+                }
+                vec![]
+            },
+        }
+    }
+}
+
+fn render_counts(title: &str, counts: &[u32], total: u32) -> Html {
+    html! {
+        <div>
+            <div>{title}</div>
+            <table style="font-family: monospace;">
+                <tr><th>{"pairs"}</th><th>{"candidates left"}</th></tr>
+                { for counts.iter().enumerate().map(|(pairs, count)| html! {
+                    <tr><td>{pairs}</td><td>{format!("{} / {}", count, total)}</td></tr>
+                }) }
+            </table>
+        </div>
+    }
+}
+
+#[derive(Properties, Clone, PartialEq)]
+pub struct Props {
+    // Only FEAL-4 itself; the attack means nothing on the key schedule or someone's upload.
+    pub available: bool,
+    pub onrecovered: Callback<Option<Recovered>>,
+}
+
+#[function_component(AttackPanel)]
+pub fn attack_panel(props: &Props) -> Html {
+    let master = use_state(random_u64);
+    let reveal = use_state(|| false);
+    let pairs_ref = NodeRef::default();
+    let search = use_mut_ref(|| None::<Search>);
+    let bridges = use_mut_ref(Vec::<WorkerBridge<SearchWorker>>::new);
+    let force_update = use_force_update();
+
+    let (a, b) = u64tou32(*master);
+    let subkeys = keygen_rounds(a, b, ROUNDS);
+    let expected = effective_key(&subkeys);
+
+    let onrun = {
+        let search = search.clone();
+        let bridges = bridges.clone();
+        let pairs_ref = pairs_ref.clone();
+        let subkeys = subkeys.clone();
+        let onrecovered = props.onrecovered.clone();
+        move |_| {
+            let count = pairs_ref.cast::<HtmlInputElement>().and_then(|input| input.value().parse::<usize>().ok()).unwrap_or(6).clamp(MIN_PAIRS, MAX_PAIRS);
+            let pairs = chosen_pairs(&subkeys, count, random_u64);
+            onrecovered.emit(None);
+
+            // New workers every run, so nothing from the last one can still come in.
+            let weak: Weak<RefCell<Vec<WorkerBridge<SearchWorker>>>> = Rc::downgrade(&bridges);
+            let fresh: Vec<WorkerBridge<SearchWorker>> = (0..WORKERS).map(|_| {
+                let search = search.clone();
+                let weak = weak.clone();
+                let force_update = force_update.clone();
+                let onrecovered = onrecovered.clone();
+                SearchWorker::spawner().callback(move |result| {
+                    let Some(bridges) = weak.upgrade() else { return };
+                    let mut search = search.borrow_mut();
+                    let Some(current) = search.as_mut() else { return };
+                    let jobs = current.receive(result);
+                    current.dispatch(&bridges.borrow(), jobs);
+                    if current.finished.is_some() {
+                        // K is k3 and the output whitening (Key index 8 in graph-4.json) rolled together.
+                        let text = match current.keys.as_slice() {
+                            [key] => Some(format!("K = 0x{:08x}", key)),
+                            [key, ..] => Some(format!("K = 0x{:08x} (+{} more)", key, current.keys.len() - 1)),
+                            [] => None,
+                        };
+                        onrecovered.emit(text.map(|text| Recovered {indices: vec![ROUNDS - 1, ROUNDS + 4], text}));
+                    }
+                    force_update.force_update();
+                }).spawn("/worker.js")
+            }).collect();
+
+            let mut fresh_search = Search::new(pairs);
+            let jobs = fresh_search.jobs(Stage::Inner, INNER_JOBS);
+            fresh_search.inner_pending = jobs.len() as u32;
+            fresh_search.dispatch(&fresh, jobs);
+            *bridges.borrow_mut() = fresh;
+            *search.borrow_mut() = Some(fresh_search);
+            force_update.force_update();
+        }
+    };
+
+    let onnewkey = {
+        let master = master.clone();
+        let search = search.clone();
+        let bridges = bridges.clone();
+        let onrecovered = props.onrecovered.clone();
+        move |_| {
+            master.set(random_u64());
+            bridges.borrow_mut().clear();
+            *search.borrow_mut() = None;
+            onrecovered.emit(None);
+        }
+    };
+
+    let onreveal = {
+        let reveal = reveal.clone();
+        move |_| reveal.set(!*reveal)
+    };
+
+    if !props.available {
+        return html! {
+            <div>
+                <h3>{"Key search"}</h3>
+                <div>{"The attack is on FEAL-4: pick it (without the key schedule or an uploaded graph)."}</div>
+            </div>
+        };
+    }
+
+    let search = search.borrow();
+    let progress = match &*search {
+        Some(current) => {
+            let elapsed = (current.finished.unwrap_or_else(js_sys::Date::now) - current.started) / 1000.0;
+            let inner_done = INNER_JOBS - current.inner_pending;
+            html! {
+                <div>
+                    <div>{format!("{} pairs, {} workers, {:.1}s", current.pairs.len(), WORKERS, elapsed)}</div>
+                    {render_counts(&format!("K0^K1, K2^K3: {} of {} jobs done", inner_done, INNER_JOBS), &current.inner_counts, CANDIDATES)}
+                    {
+                        if current.inner_pending == 0 {
+                            let total = current.inner_survivors.len() as u32 * OUTER_JOBS;
+                            render_counts(&format!("K0, K2 for {} survivors: {} of {} jobs done", current.inner_survivors.len(), total - current.outer_pending, total), &current.outer_counts, current.inner_survivors.len() as u32 * CANDIDATES)
+                        } else {
+                            html! {}
+                        }
+                    }
+                    {
+                        if current.finished.is_some() {
+                            html! {
+                                <div>
+                                    <div>{format!("{} candidates for K:", current.keys.len())}</div>
+                                    <ul style="font-family: monospace;">
+                                        { for current.keys.iter().map(|key| html! {
+                                            <li>{format!("0x{:08x} {}", key, if *key == expected { "(right)" } else { "" })}</li>
+                                        }) }
+                                    </ul>
+                                </div>
+                            }
+                        } else {
+                            html! {}
+                        }
+                    }
+                </div>
+            }
+        },
+        None => html! {},
+    };
+
+    html! {
+        <div>
+            <h3>{"Key search"}</h3>
+            <div>
+                {"Hidden key: "}
+                <span style="font-family: monospace;">{if *reveal { format!("{:016x} (K = 0x{:08x})", *master, expected) } else { "????????????????".to_string() }}</span>
+                <button onclick={onreveal}>{if *reveal { "Hide" } else { "Show" }}</button>
+                <button onclick={onnewkey}>{"New hidden key"}</button>
+            </div>
+            <label>{"Pairs:"}</label>
+            <input ref={pairs_ref} type="number" min={MIN_PAIRS.to_string()} max={MAX_PAIRS.to_string()} value="6" />
+            <button onclick={onrun}>{"Run"}</button>
+            {progress}
+        </div>
+    }
+}
@@ -0,0 +1,16 @@
+// vim: expandtab shiftwidth=4 tabstop=4:
+
+/* The web worker the attack panel farms the key search out to.
+ * trunk builds this into worker.js (see index.html).
+ */
+
+use gloo_worker::Registrable;
+
+#[path = "../feal.rs"]
+pub mod feal;
+#[path = "../attack.rs"]
+pub mod attack;
+
+fn main() {
+    attack::SearchWorker::registrar().register();
+}
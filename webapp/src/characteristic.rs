@@ -0,0 +1,222 @@
+// vim: expandtab shiftwidth=4 tabstop=4:
+
+/* A differential characteristic worked out by hand, laid over the graph:
+ * every node it says something about gets a green ring if the difference
+ * we actually see matches the prediction, and a red one if it doesn't.
+ * The JSON can name nodes directly,
+ *     {"nodes": {"12": {"difference": "0x80800000", "probability": 0.25}}}
+ * or go round by round (the input and output differences of the F box
+ * of each round, in order),
+ *     {"rounds": [{"input": "0x80800000", "output": "0x02000000", "probability": 1.0}]}
+ * or both. probability defaults to 1.
+ */
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Deserializer};
+use yew::{
+    html,
+    Html
+};
+
+use crate::computation_graph::ComputationGraph;
+use crate::feal::parse_hex_u64;
+use crate::graph::Node;
+
+fn hex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    parse_hex_u64(&value).map_err(|err| serde::de::Error::custom(format!("{}: {}", value, err)))
+}
+
+fn certain() -> f64 {
+    1.0
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Prediction {
+    #[serde(deserialize_with = "hex")]
+    pub difference: u64,
+    #[serde(default = "certain")]
+    pub probability: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct RoundPrediction {
+    #[serde(deserialize_with = "hex")]
+    pub input: u64,
+    #[serde(deserialize_with = "hex")]
+    pub output: u64,
+    #[serde(default = "certain")]
+    pub probability: f64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+pub struct Characteristic {
+    #[serde(default)]
+    pub nodes: HashMap<usize, Prediction>,
+    #[serde(default)]
+    pub rounds: Vec<RoundPrediction>,
+}
+
+impl Characteristic {
+    pub fn parse(json: &str) -> Result<Characteristic, String> {
+        serde_json::from_str(json).map_err(|err| format!("Unable to parse the characteristic: {}", err))
+    }
+
+    // Turn the rounds into node predictions for this particular graph: round i
+    // is the i'th F node, its input is the F's value and its output is the F
+    // itself (which is where the probability goes).
+    pub fn resolve(&self, nodes: &[Node]) -> Result<BTreeMap<usize, Prediction>, Vec<String>> {
+        let mut errors = vec![];
+        let mut predictions: BTreeMap<usize, Prediction> = BTreeMap::new();
+
+        let fs: Vec<(usize, usize)> = nodes.iter().filter_map(|node| match node.compgraph {
+            ComputationGraph::F {value, ..} => Some((node.id as usize, value)),
+            _ => None,
+        }).collect();
+        if self.rounds.len() > fs.len() {
+            errors.push(format!("the characteristic has {} rounds, but the graph only has {}", self.rounds.len(), fs.len()));
+        }
+        for (round, ((f, value), prediction)) in fs.iter().zip(self.rounds.iter()).enumerate() {
+            for (id, difference, probability) in [(*value, prediction.input, 1.0), (*f, prediction.output, prediction.probability)] {
+                if let Some(previous) = predictions.insert(id, Prediction {difference, probability}) {
+                    if previous.difference != difference {
+                        errors.push(format!("round {}: node {} is predicted as both 0x{:x} and 0x{:x}", round, id, previous.difference, difference));
+                    }
+                }
+            }
+        }
+
+        for (id, prediction) in self.nodes.iter() {
+            if *id >= nodes.len() {
+                errors.push(format!("node {} is out of range (there are {} nodes)", id, nodes.len()));
+            }
+            else if let Some(previous) = predictions.insert(*id, prediction.clone()) {
+                if previous.difference != prediction.difference {
+                    errors.push(format!("node {} is predicted as both 0x{:x} and 0x{:x}", id, previous.difference, prediction.difference));
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(predictions)
+        }
+        else {
+            Err(errors)
+        }
+    }
+}
+
+// A prediction next to what the current pair actually did.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Check {
+    pub id: usize,
+    pub expected: u64,
+    pub observed: u64,
+    pub probability: f64,
+    // The product of the probabilities up to and including this node.
+    pub cumulative: f64,
+}
+
+impl Check {
+    pub fn matches(&self) -> bool {
+        self.expected == self.observed
+    }
+}
+
+// The graph is topologically ordered, so going by id is going along the path.
+pub fn check(predictions: &BTreeMap<usize, Prediction>, observed: impl Fn(usize) -> u64) -> Vec<Check> {
+    let mut cumulative = 1.0;
+    predictions.iter().map(|(id, prediction)| {
+        cumulative *= prediction.probability;
+        Check {id: *id, expected: prediction.difference, observed: observed(*id), probability: prediction.probability, cumulative}
+    }).collect()
+}
+
+// Probabilities are easier to read as powers of two.
+pub fn log2str(probability: f64) -> String {
+    if probability >= 1.0 {
+        "1".to_string()
+    }
+    else if probability <= 0.0 {
+        "0".to_string()
+    }
+    else {
+        format!("2^{:.2}", probability.log2())
+    }
+}
+
+// A ring around the node, and the probabilities above it.
+pub fn render_check(node: &Node, check: &Check) -> Html {
+    let color = if check.matches() { "green" } else { "red" };
+    let text = format!("p={} Σ={}", log2str(check.probability), log2str(check.cumulative));
+    html! {
+        <>
+            <circle cx={format!("{}", node.x)} cy={format!("{}", node.y)} r={format!("{}", node.radius + 6.0)} fill="none" stroke={color} stroke-width="3" pointer-events="none" />
+            <text x={format!("{}", node.x)} y={format!("{}", node.y - node.radius - 12.0)} font-family="Arial" font-size="10" fill={color} text-anchor="middle" dy=".3em">{text}</text>
+        </>
+    }
+}
+
+pub fn render_summary(checks: &[Check]) -> Html {
+    let matched = checks.iter().filter(|check| check.matches()).count();
+    let total = checks.last().map(|check| check.cumulative).unwrap_or(1.0);
+    let first = checks.iter().find(|check| !check.matches());
+    html! {
+        <div>
+            <div>{format!("Characteristic: {} of {} predictions hold, probability {}", matched, checks.len(), log2str(total))}</div>
+            {
+                if let Some(check) = first {
+                    html! { <div class="error">{format!("First contradiction at node {}: expected 0x{:x}, saw 0x{:x}", check.id, check.expected, check.observed)}</div> }
+                } else {
+                    html! {}
+                }
+            }
+        </div>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feal4() -> Vec<Node> {
+        serde_json::from_str(include_str!("../static/graph-4.json")).unwrap()
+    }
+
+    #[test]
+    fn rounds_and_nodes() {
+        // Round 0's F is node 15, with its value from node 13.
+        let characteristic = Characteristic::parse(r#"{
+            "rounds": [{"input": "0x80800000", "output": "0x02000000"}],
+            "nodes": {"53": {"difference": "0x0", "probability": 0.25}}
+        }"#).unwrap();
+        let predictions = characteristic.resolve(&feal4()).unwrap();
+        assert_eq!(predictions.keys().copied().collect::<Vec<usize>>(), vec![13, 15, 53]);
+        assert_eq!(predictions[&15], Prediction {difference: 0x02000000, probability: 1.0});
+
+        let checks = check(&predictions, |id| if id == 53 { 1 } else { predictions[&id].difference });
+        assert!(checks[0].matches() && checks[1].matches() && !checks[2].matches());
+        assert_eq!(checks[2].cumulative, 0.25);
+        assert_eq!(log2str(checks[2].cumulative), "2^-2.00");
+    }
+
+    #[test]
+    fn malformed() {
+        assert!(Characteristic::parse(r#"{"nodes": {"3": {"difference": "0xzz"}}}"#).is_err());
+        assert!(Characteristic::parse(r#"{"nodes": {"3": {"difference": "0x0x1"}}}"#).is_err());
+        assert!(Characteristic::parse(r#"{"nodes": {"3": {"difference": "0X1"}}}"#).is_ok());
+        assert!(Characteristic::parse("[1, 2]").is_err());
+
+        let round = r#"{"input": "0x80800000", "output": "0x02000000"}"#;
+        let characteristic = Characteristic::parse(&format!(r#"{{"rounds": [{}]}}"#, [round; 5].join(", "))).unwrap();
+        let errors = characteristic.resolve(&feal4()).unwrap_err();
+        assert_eq!(errors[0], "the characteristic has 5 rounds, but the graph only has 4");
+
+        let characteristic = Characteristic::parse(r#"{"rounds": [{"input": "0x80800000", "output": "0x02000000"}],
+            "nodes": {"15": {"difference": "0x1"}, "999": {"difference": "0x1"}}}"#).unwrap();
+        let mut errors = characteristic.resolve(&feal4()).unwrap_err();
+        errors.sort();
+        assert_eq!(errors, vec!["node 15 is predicted as both 0x2000000 and 0x1", "node 999 is out of range (there are 54 nodes)"]);
+    }
+}
@@ -11,48 +11,25 @@ use std::cell::RefCell;
 
 use serde::{Deserialize, Serialize};
 use yew::{
+    Callback,
     Html,
     html
 };
 
 use crate::graph::Node;
-use crate::feal::f;
+use crate::f_detail::{render_f, render_fk};
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "label")]
 pub enum ComputationGraph {
     #[serde(rename = "plaintext")]
     Plaintext {},
 
-    #[serde(rename = "key0")]
-    Key0 {},
-
-    #[serde(rename = "key1")]
-    Key1 {},
-
-    #[serde(rename = "key2")]
-    Key2 {},
-
-    #[serde(rename = "key3")]
-    Key3 {},
-
-    #[serde(rename = "key4")]
-    Key4 {},
-
-    #[serde(rename = "key5")]
-    Key5 {},
-
-    #[serde(rename = "key6")]
-    Key6 {},
-
-    #[serde(rename = "key7")]
-    Key7 {},
-
-    #[serde(rename = "key8_11")]
-    Key8_11 {},
-
-    #[serde(rename = "key12_15")]
-    Key12_15 {},
+    // index is the first subkey: the 16 bit keys feed the rounds, and the
+    // 64 bit ones (Node::bitsize tells them apart) are the whitening keys
+    // k[index..index+4].
+    #[serde(rename = "key")]
+    Key {index: usize},
 
     #[serde(rename = "copy16")]
     Copy16 {src: usize},
@@ -82,49 +59,162 @@ pub enum ComputationGraph {
     Swap {left: usize, right: usize},
 
     #[serde(rename = "ciphertext")]
-    Ciphertext {src: usize}
+    Ciphertext {src: usize},
+
+    // The key schedule (keygen/keyround in feal.rs) is built out of these
+    // as well, plus left/right to split the master key into a and b.
+    #[serde(rename = "masterkey")]
+    MasterKey {},
+
+    // d starts out as 0.
+    #[serde(rename = "zero32")]
+    Zero32 {},
+
+    #[serde(rename = "fk")]
+    Fk {a: usize, b: usize},
+
+    // The high and low halves of fk's output, i.e. k0 and k1 of a keyround.
+    #[serde(rename = "left16")]
+    Left16 {src: usize},
+
+    #[serde(rename = "right16")]
+    Right16 {src: usize},
+
+    // Subkey k[index], as it comes out of the key schedule.
+    #[serde(rename = "subkey")]
+    Subkey {src: usize, index: usize},
+}
+
+impl ComputationGraph {
+    // The (name, node index) pairs of the incoming edges.
+    pub fn inputs(&self) -> Vec<(&'static str, usize)> {
+        match *self {
+            ComputationGraph::Plaintext {} => vec![],
+            ComputationGraph::Key {..} => vec![],
+            ComputationGraph::Copy16 {src} => vec![("src", src)],
+            ComputationGraph::Copy32 {src} => vec![("src", src)],
+            ComputationGraph::Copy64 {src} => vec![("src", src)],
+            ComputationGraph::Left {src} => vec![("src", src)],
+            ComputationGraph::Right {src} => vec![("src", src)],
+            ComputationGraph::F {subkey, value} => vec![("subkey", subkey), ("value", value)],
+            ComputationGraph::Xor32 {a, b} => vec![("a", a), ("b", b)],
+            ComputationGraph::Xor64 {a, b} => vec![("a", a), ("b", b)],
+            ComputationGraph::Swap {left, right} => vec![("left", left), ("right", right)],
+            ComputationGraph::Ciphertext {src} => vec![("src", src)],
+            ComputationGraph::MasterKey {} => vec![],
+            ComputationGraph::Zero32 {} => vec![],
+            ComputationGraph::Fk {a, b} => vec![("a", a), ("b", b)],
+            ComputationGraph::Left16 {src} => vec![("src", src)],
+            ComputationGraph::Right16 {src} => vec![("src", src)],
+            ComputationGraph::Subkey {src, ..} => vec![("src", src)],
+        }
+    }
+}
+
+// How to draw the nodes and edges (the toggles in the sidebar).
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RenderOptions {
+    // Draw each difference as a grid of bits, with the nonzero bytes
+    // (the active g0/g1 inputs) highlighted, and color the edges by
+    // whether they carry a zero difference.
+    pub bits: bool,
+    // Step through the encryption: only draw the nodes up to this id,
+    // and highlight it. None draws everything.
+    pub step: Option<usize>,
 }
 
 pub trait ComputationNode {
     fn label(&self) -> &str;
     fn node(&self) -> &Node;
-    fn eval(&self) -> (u64, u64);
-    fn render_node(&self) -> Html;
-    fn render_edges(&self) -> Html;
-}
-
-fn hexstr(value: u64, bitsize: u32) -> String {
+    // The value of a node without inputs (plaintext, keys, ...). Everything
+    // else is worked out by inspector::evaluate, once for the whole graph,
+    // and values is what it came up with, indexed by node id.
+    fn source(&self) -> Option<(u64, u64)> {None}
+    // onselect gets the node's id when it's clicked.
+    fn render_node(&self, values: &[(u64, u64)], options: &RenderOptions, onselect: &Callback<usize>) -> Html;
+    fn render_edges(&self, values: &[(u64, u64)], options: &RenderOptions) -> Html;
+    // What to show when the node is selected and there's more to it than
+    // its own value (e.g. the inside of an F box).
+    fn detail(&self, _values: &[(u64, u64)]) -> Option<Html> {None}
+}
+
+pub fn hexstr(value: u64, bitsize: u32) -> String {
     let hex_str = format!("{:x}", value);
     let required_chars = bitsize / 4;
     let padded_str = format!("{:0>width$}", hex_str, width = required_chars as usize);
     format!("0x{}", padded_str)
 }
 
-fn generic_render_node(compnode: &dyn ComputationNode) -> Html {
+// 16 bits per row, most significant bit first, so each row is two bytes.
+fn render_bit_grid(realx: f32, top: f32, bitsize: u32, differential: u64) -> Html {
+    let cell = 6.0;
+    let per_row = 16;
+    let left = realx - (per_row as f32) * cell / 2.0;
+    let bytes: Vec<Html> = (0..bitsize / 8).map(|byte| {
+        let shift = bitsize - 8 * (byte + 1);
+        let active = (differential >> shift) & 0x00ff != 0;
+        let (x, y) = (left + ((byte % 2) * 8) as f32 * cell, top + (byte / 2) as f32 * cell);
+        let fill = if active { "#fff3a0" } else { "white" };
+        html! {
+            <rect x={format!("{}", x)} y={format!("{}", y)} width={format!("{}", 8.0 * cell)} height={format!("{}", cell)} fill={fill} stroke={if active { "orange" } else { "none" }} />
+        }
+    }).collect();
+    let bits: Vec<Html> = (0..bitsize).map(|bit| {
+        let set = (differential >> (bitsize - 1 - bit)) & 0x01 != 0;
+        let (x, y) = (left + (bit % per_row) as f32 * cell, top + (bit / per_row) as f32 * cell);
+        html! {
+            <rect x={format!("{}", x + 1.0)} y={format!("{}", y + 1.0)} width={format!("{}", cell - 2.0)} height={format!("{}", cell - 2.0)} fill={if set { "red" } else { "none" }} stroke="#d3d3d3" stroke-width="0.5" />
+        }
+    }).collect();
+    html! {
+        <>
+            { for bytes.into_iter() }
+            { for bits.into_iter() }
+        </>
+    }
+}
+
+fn value(values: &[(u64, u64)], compnode: &Rc<RefCell<dyn ComputationNode>>) -> (u64, u64) {
+    values[compnode.borrow().node().id as usize]
+}
+
+fn generic_render_node(compnode: &dyn ComputationNode, values: &[(u64, u64)], options: &RenderOptions, onselect: &Callback<usize>) -> Html {
     let (realx, realy) = (compnode.node().x, compnode.node().y);
     let cx_str = format!("{}", (realx as i32));
     let cy_str = format!("{}", (realy as i32));
     let r_str = format!("{}", compnode.node().radius);
     let fill_str = format!("{}", compnode.node().color);
     let label_str = format!("{}", compnode.label());
-    let (path1, path2) = compnode.eval();
+    let (path1, path2) = values[compnode.node().id as usize];
     let y1 = realy + 36.0; // or some adjusted value for the first line
     let y2 = realy + 46.0; // for the second line
     let y3 = realy + 56.0; // for the third line
     let differential = path1 ^ path2;
+    let onclick = {
+        let onselect = onselect.clone();
+        let id = compnode.node().id as usize;
+        move |_| onselect.emit(id)
+    };
     html!(
         <>
-            <circle cx={cx_str.clone()} cy={cy_str.clone()} r={r_str} fill={fill_str} />
-            <text x={cx_str.clone()} y={cy_str.clone()} font-family="Arial" font-size="10" fill="black" text-anchor="middle" dy=".3em">{label_str}</text>
+            <circle cx={cx_str.clone()} cy={cy_str.clone()} r={r_str} fill={fill_str} style="cursor: pointer;" onclick={onclick} />
+            <text x={cx_str.clone()} y={cy_str.clone()} font-family="Arial" font-size="10" fill="black" text-anchor="middle" dy=".3em" pointer-events="none">{label_str}</text>
             <rect x={format!("{}", realx-56.0)} y={format!("{}", realy+21.)} width="112" height="50" rx="10" ry="10" fill="white" stroke="black"/>
             <text x={format!("{}", realx)} y={format!("{}", y1)} font-family="Arial" font-size="10" fill="black" text-anchor="middle" dy=".3em">{hexstr(path1, compnode.node().bitsize)}</text>
             <text x={format!("{}", realx)} y={format!("{}", y2)} font-family="Arial" font-size="10" fill="black" text-anchor="middle" dy=".3em">{hexstr(path2, compnode.node().bitsize)}</text>
             <text x={format!("{}", realx)} y={format!("{}", y3)} font-family="Arial" font-size="10" fill="red" text-anchor="middle" dy=".3em">{hexstr(differential, compnode.node().bitsize)}</text>
+            {
+                if options.bits {
+                    render_bit_grid(realx, realy + 74.0, compnode.node().bitsize, differential)
+                } else {
+                    html! {}
+                }
+            }
         </>
     )
 }
 
-fn generic_render_edge(dst: &dyn ComputationNode, src: &dyn ComputationNode) -> Html {
+fn generic_render_edge(dst: &dyn ComputationNode, src: &dyn ComputationNode, values: &[(u64, u64)], options: &RenderOptions) -> Html {
     let markersz = 20.0;
 
     let theta = f32::atan2(dst.node().y - src.node().y, dst.node().x - src.node().x);
@@ -133,13 +223,21 @@ fn generic_render_edge(dst: &dyn ComputationNode, src: &dyn ComputationNode) ->
 
     let d = format!("M {},{} L {},{}", srcx, srcy, dstx, dsty);
 
+    // The markers are defined in GraphImpl::render.
+    let (stroke, marker) = if !options.bits {
+        ("black", "url(#arrowhead)")
+    } else {
+        let (value1, value2) = values[src.node().id as usize];
+        if value1 == value2 { ("#bbbbbb", "url(#arrowhead-zero)") } else { ("red", "url(#arrowhead-active)") }
+    };
+
     html! {
-        <path d={d} fill="none" stroke="black" stroke-width="2" marker-end="url(#arrowhead)" />
+        <path d={d} fill="none" stroke={stroke} stroke-width="2" marker-end={marker} />
     }
 }
 
-fn generic_render_edges(dst: &dyn ComputationNode, srcs: Vec<Rc<RefCell<dyn ComputationNode>>>) -> Html {
-    let edges: Vec<Html> = srcs.iter().map(|src| generic_render_edge(dst, &*src.borrow())).collect();
+fn generic_render_edges(dst: &dyn ComputationNode, srcs: Vec<Rc<RefCell<dyn ComputationNode>>>, values: &[(u64, u64)], options: &RenderOptions) -> Html {
+    let edges: Vec<Html> = srcs.iter().map(|src| generic_render_edge(dst, &*src.borrow(), values, options)).collect();
     html! {
         <>
             { for edges.into_iter() }
@@ -156,161 +254,37 @@ pub struct Plaintext {
 impl ComputationNode for Plaintext {
     fn label(&self) -> &str {"plaintext"}
     fn node(&self) -> &Node {&self.node}
-    fn eval(&self) -> (u64, u64) {
-        (self.value, self.value ^ self.differential)
-    }
-    fn render_node(&self) -> Html {generic_render_node(self)}
-    fn render_edges(&self) -> Html {generic_render_edges(self, vec![])}
-}
-
-pub struct Key0 {
-    pub node: Node,
-    pub key: u16,
-}
-
-impl ComputationNode for Key0 {
-    fn label(&self) -> &str {"key0"}
-    fn node(&self) -> &Node {&self.node}
-    fn eval(&self) -> (u64, u64) {
-        (self.key as u64, self.key as u64)
-    }
-    fn render_node(&self) -> Html {generic_render_node(self)}
-    fn render_edges(&self) -> Html {generic_render_edges(self, vec![])}
-}
-
-pub struct Key1 {
-    pub node: Node,
-    pub key: u16,
-}
-
-impl ComputationNode for Key1 {
-    fn label(&self) -> &str {"key1"}
-    fn node(&self) -> &Node {&self.node}
-    fn eval(&self) -> (u64, u64) {
-        (self.key as u64, self.key as u64)
-    }
-    fn render_node(&self) -> Html {generic_render_node(self)}
-    fn render_edges(&self) -> Html {generic_render_edges(self, vec![])}
-}
-
-pub struct Key2 {
-    pub node: Node,
-    pub key: u16,
-}
-
-impl ComputationNode for Key2 {
-    fn label(&self) -> &str {"key2"}
-    fn node(&self) -> &Node {&self.node}
-    fn eval(&self) -> (u64, u64) {
-        (self.key as u64, self.key as u64)
-    }
-    fn render_node(&self) -> Html {generic_render_node(self)}
-    fn render_edges(&self) -> Html {generic_render_edges(self, vec![])}
-}
-
-pub struct Key3 {
-    pub node: Node,
-    pub key: u16,
-}
-
-impl ComputationNode for Key3 {
-    fn label(&self) -> &str {"key3"}
-    fn node(&self) -> &Node {&self.node}
-    fn eval(&self) -> (u64, u64) {
-        (self.key as u64, self.key as u64)
-    }
-    fn render_node(&self) -> Html {generic_render_node(self)}
-    fn render_edges(&self) -> Html {generic_render_edges(self, vec![])}
-}
-
-pub struct Key4 {
-    pub node: Node,
-    pub key: u16,
-}
-
-impl ComputationNode for Key4 {
-    fn label(&self) -> &str {"key4"}
-    fn node(&self) -> &Node {&self.node}
-    fn eval(&self) -> (u64, u64) {
-        (self.key as u64, self.key as u64)
-    }
-    fn render_node(&self) -> Html {generic_render_node(self)}
-    fn render_edges(&self) -> Html {generic_render_edges(self, vec![])}
-}
-
-pub struct Key5 {
-    pub node: Node,
-    pub key: u16,
-}
-
-impl ComputationNode for Key5 {
-    fn label(&self) -> &str {"key5"}
-    fn node(&self) -> &Node {&self.node}
-    fn eval(&self) -> (u64, u64) {
-        (self.key as u64, self.key as u64)
-    }
-    fn render_node(&self) -> Html {generic_render_node(self)}
-    fn render_edges(&self) -> Html {generic_render_edges(self, vec![])}
-}
-
-pub struct Key6 {
-    pub node: Node,
-    pub key: u16,
-}
-
-impl ComputationNode for Key6 {
-    fn label(&self) -> &str {"key6"}
-    fn node(&self) -> &Node {&self.node}
-    fn eval(&self) -> (u64, u64) {
-        (self.key as u64, self.key as u64)
+    fn source(&self) -> Option<(u64, u64)> {
+        Some((self.value, self.value ^ self.differential))
     }
-    fn render_node(&self) -> Html {generic_render_node(self)}
-    fn render_edges(&self) -> Html {generic_render_edges(self, vec![])}
+    fn render_node(&self, values: &[(u64, u64)], options: &RenderOptions, onselect: &Callback<usize>) -> Html {generic_render_node(self, values, options, onselect)}
+    fn render_edges(&self, values: &[(u64, u64)], options: &RenderOptions) -> Html {generic_render_edges(self, vec![], values, options)}
 }
 
-pub struct Key7 {
-    pub node: Node,
-    pub key: u16,
-}
-
-impl ComputationNode for Key7 {
-    fn label(&self) -> &str {"key7"}
-    fn node(&self) -> &Node {&self.node}
-    fn eval(&self) -> (u64, u64) {
-        (self.key as u64, self.key as u64)
-    }
-    fn render_node(&self) -> Html {generic_render_node(self)}
-    fn render_edges(&self) -> Html {generic_render_edges(self, vec![])}
-}
-
-pub struct Key8_11 {
+pub struct Key {
     pub node: Node,
+    pub label: String,
     pub key: u64,
 }
 
-impl ComputationNode for Key8_11 {
-    fn label(&self) -> &str {"key8-11"}
-    fn node(&self) -> &Node {&self.node}
-    fn eval(&self) -> (u64, u64) {
-        (self.key, self.key)
+impl Key {
+    pub fn new(node: Node, index: usize, key: u64) -> Key {
+        let label = match node.bitsize {
+            16 => format!("key{}", index),
+            bitsize => format!("key{}-{}", index, index + (bitsize as usize) / 16 - 1),
+        };
+        Key{node, label, key}
     }
-    fn render_node(&self) -> Html {generic_render_node(self)}
-    fn render_edges(&self) -> Html {generic_render_edges(self, vec![])}
 }
 
-pub struct Key12_15 {
-    pub node: Node,
-    pub key: u64,
-}
-
-impl ComputationNode for Key12_15 {
-    fn label(&self) -> &str {"key12-15"}
+impl ComputationNode for Key {
+    fn label(&self) -> &str {&self.label}
     fn node(&self) -> &Node {&self.node}
-    fn eval(&self) -> (u64, u64) {
-        (self.key, self.key)
+    fn source(&self) -> Option<(u64, u64)> {
+        Some((self.key, self.key))
     }
-    fn render_node(&self) -> Html {generic_render_node(self)}
-    fn render_edges(&self) -> Html {generic_render_edges(self, vec![])}
+    fn render_node(&self, values: &[(u64, u64)], options: &RenderOptions, onselect: &Callback<usize>) -> Html {generic_render_node(self, values, options, onselect)}
+    fn render_edges(&self, values: &[(u64, u64)], options: &RenderOptions) -> Html {generic_render_edges(self, vec![], values, options)}
 }
 
 pub struct Copy16 {
@@ -321,12 +295,8 @@ pub struct Copy16 {
 impl ComputationNode for Copy16 {
     fn label(&self) -> &str {"copy16"}
     fn node(&self) -> &Node {&self.node}
-    fn eval(&self) -> (u64, u64) {
-        let (src1, src2) = self.src.borrow().eval();
-        (src1 & 0x00ffff, src2 & 0x00ffff)
-    }
-    fn render_node(&self) -> Html {generic_render_node(self)}
-    fn render_edges(&self) -> Html {generic_render_edges(self, vec![self.src.clone()])}
+    fn render_node(&self, values: &[(u64, u64)], options: &RenderOptions, onselect: &Callback<usize>) -> Html {generic_render_node(self, values, options, onselect)}
+    fn render_edges(&self, values: &[(u64, u64)], options: &RenderOptions) -> Html {generic_render_edges(self, vec![self.src.clone()], values, options)}
 }
 
 pub struct Copy32 {
@@ -337,12 +307,8 @@ pub struct Copy32 {
 impl ComputationNode for Copy32 {
     fn label(&self) -> &str {"copy32"}
     fn node(&self) -> &Node {&self.node}
-    fn eval(&self) -> (u64, u64) {
-        let (src1, src2) = self.src.borrow().eval();
-        (src1 & 0x00ffffffff, src2 & 0x00ffffffff)
-    }
-    fn render_node(&self) -> Html {generic_render_node(self)}
-    fn render_edges(&self) -> Html {generic_render_edges(self, vec![self.src.clone()])}
+    fn render_node(&self, values: &[(u64, u64)], options: &RenderOptions, onselect: &Callback<usize>) -> Html {generic_render_node(self, values, options, onselect)}
+    fn render_edges(&self, values: &[(u64, u64)], options: &RenderOptions) -> Html {generic_render_edges(self, vec![self.src.clone()], values, options)}
 }
 
 pub struct Copy64 {
@@ -353,11 +319,8 @@ pub struct Copy64 {
 impl ComputationNode for Copy64 {
     fn label(&self) -> &str {"copy64"}
     fn node(&self) -> &Node {&self.node}
-    fn eval(&self) -> (u64, u64) {
-        self.src.borrow().eval()
-    }
-    fn render_node(&self) -> Html {generic_render_node(self)}
-    fn render_edges(&self) -> Html {generic_render_edges(self, vec![self.src.clone()])}
+    fn render_node(&self, values: &[(u64, u64)], options: &RenderOptions, onselect: &Callback<usize>) -> Html {generic_render_node(self, values, options, onselect)}
+    fn render_edges(&self, values: &[(u64, u64)], options: &RenderOptions) -> Html {generic_render_edges(self, vec![self.src.clone()], values, options)}
 }
 
 pub struct Left {
@@ -368,12 +331,8 @@ pub struct Left {
 impl ComputationNode for Left {
     fn label(&self) -> &str {"left"}
     fn node(&self) -> &Node {&self.node}
-    fn eval(&self) -> (u64, u64) {
-        let (src1, src2) = self.src.borrow().eval();
-        ((src1 >> 32) & 0x00ffffffff, (src2 >> 32) & 0x00ffffffff)
-    }
-    fn render_node(&self) -> Html {generic_render_node(self)}
-    fn render_edges(&self) -> Html {generic_render_edges(self, vec![self.src.clone()])}
+    fn render_node(&self, values: &[(u64, u64)], options: &RenderOptions, onselect: &Callback<usize>) -> Html {generic_render_node(self, values, options, onselect)}
+    fn render_edges(&self, values: &[(u64, u64)], options: &RenderOptions) -> Html {generic_render_edges(self, vec![self.src.clone()], values, options)}
 }
 
 pub struct Right {
@@ -384,12 +343,8 @@ pub struct Right {
 impl ComputationNode for Right {
     fn label(&self) -> &str {"right"}
     fn node(&self) -> &Node {&self.node}
-    fn eval(&self) -> (u64, u64) {
-        let (src1, src2) = self.src.borrow().eval();
-        (src1 & 0x00ffffffff, src2 & 0x00ffffffff)
-    }
-    fn render_node(&self) -> Html {generic_render_node(self)}
-    fn render_edges(&self) -> Html {generic_render_edges(self, vec![self.src.clone()])}
+    fn render_node(&self, values: &[(u64, u64)], options: &RenderOptions, onselect: &Callback<usize>) -> Html {generic_render_node(self, values, options, onselect)}
+    fn render_edges(&self, values: &[(u64, u64)], options: &RenderOptions) -> Html {generic_render_edges(self, vec![self.src.clone()], values, options)}
 }
 
 pub struct F{
@@ -401,21 +356,11 @@ pub struct F{
 impl ComputationNode for F {
     fn label(&self) -> &str {"F"}
     fn node(&self) -> &Node {&self.node}
-    fn eval(&self) -> (u64, u64) {
-        let (subkey1, subkey2) = self.subkey.borrow().eval();
-        let (value1, value2) = self.value.borrow().eval();
-        let subkey1 = subkey1 as u16;
-        let subkey2 = subkey2 as u16;
-        let value1 = value1 as u32;
-        let value2 = value2 as u32;
-
-        let output1 = f(subkey1, value1);
-        let output2 = f(subkey2, value2);
-
-        (output1.into(), output2.into())
+    fn render_node(&self, values: &[(u64, u64)], options: &RenderOptions, onselect: &Callback<usize>) -> Html {generic_render_node(self, values, options, onselect)}
+    fn render_edges(&self, values: &[(u64, u64)], options: &RenderOptions) -> Html {generic_render_edges(self, vec![self.subkey.clone(), self.value.clone()], values, options)}
+    fn detail(&self, values: &[(u64, u64)]) -> Option<Html> {
+        Some(render_f(value(values, &self.subkey), value(values, &self.value)))
     }
-    fn render_node(&self) -> Html {generic_render_node(self)}
-    fn render_edges(&self) -> Html {generic_render_edges(self, vec![self.subkey.clone(), self.value.clone()])}
 }
 
 pub struct Xor32 {
@@ -427,13 +372,8 @@ pub struct Xor32 {
 impl ComputationNode for Xor32 {
     fn label(&self) -> &str {"xor32"}
     fn node(&self) -> &Node {&self.node}
-    fn eval(&self) -> (u64, u64) {
-        let (a1, a2) = self.a.borrow().eval();
-        let (b1, b2) = self.b.borrow().eval();
-        ((a1 ^ b1) & 0x00ffffffff, (a2 ^ b2) & 0x00ffffffff)
-    }
-    fn render_node(&self) -> Html {generic_render_node(self)}
-    fn render_edges(&self) -> Html {generic_render_edges(self, vec![self.a.clone(), self.b.clone()])}
+    fn render_node(&self, values: &[(u64, u64)], options: &RenderOptions, onselect: &Callback<usize>) -> Html {generic_render_node(self, values, options, onselect)}
+    fn render_edges(&self, values: &[(u64, u64)], options: &RenderOptions) -> Html {generic_render_edges(self, vec![self.a.clone(), self.b.clone()], values, options)}
 }
 
 pub struct Xor64 {
@@ -445,13 +385,8 @@ pub struct Xor64 {
 impl ComputationNode for Xor64 {
     fn label(&self) -> &str {"xor64"}
     fn node(&self) -> &Node {&self.node}
-    fn eval(&self) -> (u64, u64) {
-        let (a1, a2) = self.a.borrow().eval();
-        let (b1, b2) = self.b.borrow().eval();
-        (a1 ^ b1, a2 ^ b2)
-    }
-    fn render_node(&self) -> Html {generic_render_node(self)}
-    fn render_edges(&self) -> Html {generic_render_edges(self, vec![self.a.clone(), self.b.clone()])}
+    fn render_node(&self, values: &[(u64, u64)], options: &RenderOptions, onselect: &Callback<usize>) -> Html {generic_render_node(self, values, options, onselect)}
+    fn render_edges(&self, values: &[(u64, u64)], options: &RenderOptions) -> Html {generic_render_edges(self, vec![self.a.clone(), self.b.clone()], values, options)}
 }
 
 pub struct Swap {
@@ -463,19 +398,8 @@ pub struct Swap {
 impl ComputationNode for Swap {
     fn label(&self) -> &str {"swap"}
     fn node(&self) -> &Node {&self.node}
-    fn eval(&self) -> (u64, u64) {
-        let (left1, left2) = self.left.borrow().eval();
-        let (right1, right2) = self.right.borrow().eval();
-
-        let left1 = left1 & 0x00ffffffff;
-        let left2 = left2 & 0x00ffffffff;
-        let right1 = right1 & 0x00ffffffff;
-        let right2 = right2 & 0x00ffffffff;
-
-        (((right1 << 32) | left1), ((right2 << 32) | left2))
-    }
-    fn render_node(&self) -> Html {generic_render_node(self)}
-    fn render_edges(&self) -> Html {generic_render_edges(self, vec![self.left.clone(), self.right.clone()])}
+    fn render_node(&self, values: &[(u64, u64)], options: &RenderOptions, onselect: &Callback<usize>) -> Html {generic_render_node(self, values, options, onselect)}
+    fn render_edges(&self, values: &[(u64, u64)], options: &RenderOptions) -> Html {generic_render_edges(self, vec![self.left.clone(), self.right.clone()], values, options)}
 }
 
 pub struct Ciphertext {
@@ -486,9 +410,97 @@ pub struct Ciphertext {
 impl ComputationNode for Ciphertext {
     fn label(&self) -> &str {"ciphertext"}
     fn node(&self) -> &Node {&self.node}
-    fn eval(&self) -> (u64, u64) {
-        self.src.borrow().eval()
+    fn render_node(&self, values: &[(u64, u64)], options: &RenderOptions, onselect: &Callback<usize>) -> Html {generic_render_node(self, values, options, onselect)}
+    fn render_edges(&self, values: &[(u64, u64)], options: &RenderOptions) -> Html {generic_render_edges(self, vec![self.src.clone()], values, options)}
+}
+
+// The key schedule's input: the pair is key and key ^ differential, so
+// we can watch a key difference go through to the subkeys.
+pub struct MasterKey {
+    pub node: Node,
+    pub differential: u64,
+    pub value: u64,
+}
+
+impl ComputationNode for MasterKey {
+    fn label(&self) -> &str {"masterkey"}
+    fn node(&self) -> &Node {&self.node}
+    fn source(&self) -> Option<(u64, u64)> {
+        Some((self.value, self.value ^ self.differential))
     }
-    fn render_node(&self) -> Html {generic_render_node(self)}
-    fn render_edges(&self) -> Html {generic_render_edges(self, vec![self.src.clone()])}
+    fn render_node(&self, values: &[(u64, u64)], options: &RenderOptions, onselect: &Callback<usize>) -> Html {generic_render_node(self, values, options, onselect)}
+    fn render_edges(&self, values: &[(u64, u64)], options: &RenderOptions) -> Html {generic_render_edges(self, vec![], values, options)}
+}
+
+pub struct Zero32 {
+    pub node: Node,
+}
+
+impl ComputationNode for Zero32 {
+    fn label(&self) -> &str {"zero32"}
+    fn node(&self) -> &Node {&self.node}
+    fn source(&self) -> Option<(u64, u64)> {
+        Some((0, 0))
+    }
+    fn render_node(&self, values: &[(u64, u64)], options: &RenderOptions, onselect: &Callback<usize>) -> Html {generic_render_node(self, values, options, onselect)}
+    fn render_edges(&self, values: &[(u64, u64)], options: &RenderOptions) -> Html {generic_render_edges(self, vec![], values, options)}
+}
+
+pub struct Fk {
+    pub node: Node,
+    pub a: Rc<RefCell<dyn ComputationNode>>,
+    pub b: Rc<RefCell<dyn ComputationNode>>,
+}
+
+impl ComputationNode for Fk {
+    fn label(&self) -> &str {"fk"}
+    fn node(&self) -> &Node {&self.node}
+    fn render_node(&self, values: &[(u64, u64)], options: &RenderOptions, onselect: &Callback<usize>) -> Html {generic_render_node(self, values, options, onselect)}
+    fn render_edges(&self, values: &[(u64, u64)], options: &RenderOptions) -> Html {generic_render_edges(self, vec![self.a.clone(), self.b.clone()], values, options)}
+    fn detail(&self, values: &[(u64, u64)]) -> Option<Html> {
+        Some(render_fk(value(values, &self.a), value(values, &self.b)))
+    }
+}
+
+pub struct Left16 {
+    pub node: Node,
+    pub src: Rc<RefCell<dyn ComputationNode>>,
+}
+
+impl ComputationNode for Left16 {
+    fn label(&self) -> &str {"left16"}
+    fn node(&self) -> &Node {&self.node}
+    fn render_node(&self, values: &[(u64, u64)], options: &RenderOptions, onselect: &Callback<usize>) -> Html {generic_render_node(self, values, options, onselect)}
+    fn render_edges(&self, values: &[(u64, u64)], options: &RenderOptions) -> Html {generic_render_edges(self, vec![self.src.clone()], values, options)}
+}
+
+pub struct Right16 {
+    pub node: Node,
+    pub src: Rc<RefCell<dyn ComputationNode>>,
+}
+
+impl ComputationNode for Right16 {
+    fn label(&self) -> &str {"right16"}
+    fn node(&self) -> &Node {&self.node}
+    fn render_node(&self, values: &[(u64, u64)], options: &RenderOptions, onselect: &Callback<usize>) -> Html {generic_render_node(self, values, options, onselect)}
+    fn render_edges(&self, values: &[(u64, u64)], options: &RenderOptions) -> Html {generic_render_edges(self, vec![self.src.clone()], values, options)}
+}
+
+pub struct Subkey {
+    pub node: Node,
+    pub label: String,
+    pub src: Rc<RefCell<dyn ComputationNode>>,
+}
+
+impl Subkey {
+    pub fn new(node: Node, index: usize, src: Rc<RefCell<dyn ComputationNode>>) -> Subkey {
+        Subkey{node, label: format!("k{}", index), src}
+    }
+}
+
+impl ComputationNode for Subkey {
+    fn label(&self) -> &str {&self.label}
+    fn node(&self) -> &Node {&self.node}
+    fn render_node(&self, values: &[(u64, u64)], options: &RenderOptions, onselect: &Callback<usize>) -> Html {generic_render_node(self, values, options, onselect)}
+    fn render_edges(&self, values: &[(u64, u64)], options: &RenderOptions) -> Html {generic_render_edges(self, vec![self.src.clone()], values, options)}
 }
@@ -0,0 +1,200 @@
+// vim: expandtab shiftwidth=4 tabstop=4:
+
+/* The difference distribution table of g0 and g1 as a heatmap: row is the
+ * difference on one input (the other input's difference is 0, and gx
+ * doesn't care which input it is), column is the output difference, and
+ * the color is how many of the 2^16 (a, b) pairs do that.
+ *
+ * Clicking a cell goes back to the graph with a differential that puts
+ * that difference into exactly one g box of the first round's F:
+ *     g0: F input difference (d, d, 0, 0). a0 ^ a1 cancels in v1, so v3 is
+ *         unchanged and only v5 = g0(a0, v3) sees it, on f0.
+ *     g1: F input difference (0, 0, d, d). a2 ^ a3 cancels in v2, so only
+ *         v6 = g1(a3, v4) sees it, on f3.
+ * The plaintext's left half has difference 0 and its right half is the
+ * F input difference, since round 0's F sees right ^ left.
+ * That's how 0x80 -> 0x02 in g0 becomes 0x80800000 -> 0x02000000 in F.
+ */
+
+use wasm_bindgen::{Clamped, JsCast};
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, ImageData};
+use yew::{
+    Callback,
+    function_component,
+    html,
+    Html,
+    MouseEvent,
+    NodeRef,
+    Properties,
+    use_effect_with_deps,
+    use_memo,
+    use_state
+};
+
+use crate::feal::{gx, u8tou32};
+
+// Each cell is this many pixels on the screen.
+const CELL: i32 = 3;
+const PAIRS: u32 = 1 << 16;
+
+// counts[din * 256 + dout]
+fn table(x: u8) -> Vec<u32> {
+    let mut counts = vec![0; 256 * 256];
+    for din in 0..=255u8 {
+        for a in 0..=255u8 {
+            for b in 0..=255u8 {
+                let dout = gx(x, a, b) ^ gx(x, a ^ din, b);
+                counts[din as usize * 256 + dout as usize] += 1;
+            }
+        }
+    }
+    counts
+}
+
+// White for 0, then darker on a log scale up to black for all of them.
+fn shade(count: u32) -> [u8; 4] {
+    if count == 0 {
+        return [255, 255, 255, 255];
+    }
+    let level = ((count as f64).log2() + 1.0) / ((PAIRS as f64).log2() + 1.0);
+    let red = (255.0 * (1.0 - level)) as u8;
+    let other = (200.0 * (1.0 - level)) as u8;
+    [red, other, other, 255]
+}
+
+fn draw(canvas: &HtmlCanvasElement, counts: &[u32]) -> Option<()> {
+    let context: CanvasRenderingContext2d = canvas.get_context("2d").ok()??.dyn_into().ok()?;
+    let pixels: Vec<u8> = counts.iter().flat_map(|count| shade(*count)).collect();
+    let image = ImageData::new_with_u8_clamped_array(Clamped(&pixels), 256).ok()?;
+    context.put_image_data(&image, 0.0, 0.0).ok()
+}
+
+// The F input and output differences a cell stands for (see the top of the file).
+pub fn f_differences(x: u8, din: u8, dout: u8) -> (u32, u32) {
+    match x {
+        0 => (u8tou32(din, din, 0, 0), u8tou32(dout, 0, 0, 0)),
+        _ => (u8tou32(0, 0, din, din), u8tou32(0, 0, 0, dout)),
+    }
+}
+
+// Which cell the mouse is on.
+fn cell(event: &MouseEvent) -> Option<(u8, u8)> {
+    let (column, row) = (event.offset_x() / CELL, event.offset_y() / CELL);
+    if (0..256).contains(&column) && (0..256).contains(&row) {
+        Some((row as u8, column as u8))
+    }
+    else {
+        None
+    }
+}
+
+#[derive(Properties, Clone, PartialEq)]
+pub struct Props {
+    // Called with the plaintext differential when a cell is clicked.
+    pub onselect: Callback<u64>,
+}
+
+#[function_component(Ddt)]
+pub fn ddt(props: &Props) -> Html {
+    let x = use_state(|| 0u8);
+    let hovered = use_state(|| None::<(u8, u8)>);
+    let canvas_ref = NodeRef::default();
+    let counts = use_memo(|x| table(*x), *x);
+
+    {
+        let canvas_ref = canvas_ref.clone();
+        let counts = counts.clone();
+        use_effect_with_deps(move |_| {
+            if let Some(canvas) = canvas_ref.cast::<HtmlCanvasElement>() {
+                draw(&canvas, &counts);
+            }
+            || ()
+        }, *x);
+    }
+
+    let onbox = |which: u8| {
+        let x = x.clone();
+        let hovered = hovered.clone();
+        move |_| {
+            x.set(which);
+            hovered.set(None);
+        }
+    };
+    let onmousemove = {
+        let hovered = hovered.clone();
+        move |event: MouseEvent| hovered.set(cell(&event))
+    };
+    let onmouseleave = {
+        let hovered = hovered.clone();
+        move |_| hovered.set(None)
+    };
+    let onclick = {
+        let x = x.clone();
+        let onselect = props.onselect.clone();
+        move |event: MouseEvent| {
+            if let Some((din, dout)) = cell(&event) {
+                let (fin, _) = f_differences(*x, din, dout);
+                onselect.emit(fin as u64);
+            }
+        }
+    };
+
+    let info = match *hovered {
+        Some((din, dout)) => {
+            let count = counts[din as usize * 256 + dout as usize];
+            let (fin, fout) = f_differences(*x, din, dout);
+            html! {
+                <div style="font-family: monospace;">
+                    <div>{format!("g{}: 0x{:02x} -> 0x{:02x}: {} of {} pairs (p = {:.4})", *x, din, dout, count, PAIRS, count as f64 / PAIRS as f64)}</div>
+                    <div>{format!("F: 0x{:08x} -> 0x{:08x}, differential 0x{:016x}", fin, fout, fin)}</div>
+                </div>
+            }
+        },
+        None => html! { <div>{"Hover over a cell for its count, click it to try it on the graph."}</div> },
+    };
+    let size = format!("{}px", 256 * CELL);
+
+    html! {
+        <div>
+            <label>
+                <input type="radio" name="gx" checked={*x == 0} onchange={onbox(0)} />
+                {"g0"}
+            </label>
+            <label>
+                <input type="radio" name="gx" checked={*x == 1} onchange={onbox(1)} />
+                {"g1"}
+            </label>
+            <div>{"Rows are the input difference, columns the output difference (both 0x00 to 0xff)."}</div>
+            {info}
+            <canvas ref={canvas_ref} width="256" height="256" style={format!("width: {}; height: {}; image-rendering: pixelated; border: 1px solid black; cursor: crosshair;", size, size)} onmousemove={onmousemove} onmouseleave={onmouseleave} onclick={onclick} />
+        </div>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn g0_row_0x80() {
+        // Every pair with input difference 0x80 comes out with 0x02.
+        let counts = table(0);
+        assert_eq!(counts[0x80 * 256 + 0x02], PAIRS);
+        assert_eq!(counts[0], PAIRS);
+    }
+
+    #[test]
+    fn rows_count_every_pair() {
+        for x in [0, 1] {
+            for row in table(x).chunks(256) {
+                assert_eq!(row.iter().sum::<u32>(), PAIRS);
+            }
+        }
+    }
+
+    #[test]
+    fn f_differences_of_a_cell() {
+        assert_eq!(f_differences(0, 0x80, 0x02), (0x80800000, 0x02000000));
+        assert_eq!(f_differences(1, 0x80, 0x02), (0x00008080, 0x00000002));
+    }
+}
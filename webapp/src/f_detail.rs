@@ -0,0 +1,185 @@
+// vim: expandtab shiftwidth=4 tabstop=4:
+
+/* "Zoom into" an F box: draw the byte-wise dataflow of f (as fyoutube
+ * in feal.rs describes it) and of fk (for the key schedule), with the
+ * value pair and difference on every internal wire.
+ * The wires are listed in topological order, and srcs are indices into the same list.
+ */
+
+use yew::{
+    html,
+    Html
+};
+
+use crate::feal::{g0, g1, u16tou8, u32tou8};
+
+struct Wire {
+    label: &'static str,
+    srcs: &'static [usize],
+}
+
+const F_WIRES: [Wire; 18] = [
+    Wire {label: "a0", srcs: &[]},
+    Wire {label: "a1", srcs: &[]},
+    Wire {label: "b0", srcs: &[]},
+    Wire {label: "b1", srcs: &[]},
+    Wire {label: "a2", srcs: &[]},
+    Wire {label: "a3", srcs: &[]},
+    Wire {label: "b = a1^b0", srcs: &[1, 2]},
+    Wire {label: "c = a2^b1", srcs: &[4, 3]},
+    Wire {label: "v1 = a0^b", srcs: &[0, 6]},
+    Wire {label: "v2 = c^a3", srcs: &[7, 5]},
+    Wire {label: "v3 = g1(v1, v2)", srcs: &[8, 9]},
+    Wire {label: "v4 = g0(v2, v3)", srcs: &[9, 10]},
+    Wire {label: "v5 = g0(a0, v3)", srcs: &[0, 10]},
+    Wire {label: "v6 = g1(a3, v4)", srcs: &[5, 11]},
+    Wire {label: "f0 = v5", srcs: &[12]},
+    Wire {label: "f1 = v3", srcs: &[10]},
+    Wire {label: "f2 = v4", srcs: &[11]},
+    Wire {label: "f3 = v6", srcs: &[13]},
+];
+
+// The values on F_WIRES for f(b, a).
+fn f_values(b: u16, a: u32) -> Vec<u8> {
+    let (b0, b1) = u16tou8(b);
+    let (a0, a1, a2, a3) = u32tou8(a);
+    let bb = a1 ^ b0;
+    let c = a2 ^ b1;
+    let v1 = a0 ^ bb;
+    let v2 = c ^ a3;
+    let v3 = g1(v1, v2);
+    let v4 = g0(v2, v3);
+    let v5 = g0(a0, v3);
+    let v6 = g1(a3, v4);
+    vec![a0, a1, b0, b1, a2, a3, bb, c, v1, v2, v3, v4, v5, v6, v5, v3, v4, v6]
+}
+
+const FK_WIRES: [Wire; 20] = [
+    Wire {label: "a0", srcs: &[]},
+    Wire {label: "a1", srcs: &[]},
+    Wire {label: "a2", srcs: &[]},
+    Wire {label: "a3", srcs: &[]},
+    Wire {label: "b0", srcs: &[]},
+    Wire {label: "b1", srcs: &[]},
+    Wire {label: "b2", srcs: &[]},
+    Wire {label: "b3", srcs: &[]},
+    Wire {label: "v1 = a0^a1", srcs: &[0, 1]},
+    Wire {label: "v2 = a2^a3", srcs: &[2, 3]},
+    Wire {label: "v3 = v2^b0", srcs: &[9, 4]},
+    Wire {label: "v4 = g1(v1, v3)", srcs: &[8, 10]},
+    Wire {label: "v5 = v4^b1", srcs: &[11, 5]},
+    Wire {label: "v6 = g0(v2, v5)", srcs: &[9, 12]},
+    Wire {label: "v7 = v6^b3", srcs: &[13, 7]},
+    Wire {label: "v8 = v4^b2", srcs: &[11, 6]},
+    Wire {label: "f0 = g0(a0, v8)", srcs: &[0, 15]},
+    Wire {label: "f1 = v4", srcs: &[11]},
+    Wire {label: "f2 = v6", srcs: &[13]},
+    Wire {label: "f3 = g1(a3, v7)", srcs: &[3, 14]},
+];
+
+// The values on FK_WIRES for fk32(a, b).
+fn fk_values(a: u32, b: u32) -> Vec<u8> {
+    let (a0, a1, a2, a3) = u32tou8(a);
+    let (b0, b1, b2, b3) = u32tou8(b);
+    let v1 = a0 ^ a1;
+    let v2 = a2 ^ a3;
+    let v3 = v2 ^ b0;
+    let v4 = g1(v1, v3);
+    let v5 = v4 ^ b1;
+    let v6 = g0(v2, v5);
+    let v7 = v6 ^ b3;
+    let v8 = v4 ^ b2;
+    let f0 = g0(a0, v8);
+    let f3 = g1(a3, v7);
+    vec![a0, a1, a2, a3, b0, b1, b2, b3, v1, v2, v3, v4, v5, v6, v7, v8, f0, v4, v6, f3]
+}
+
+// Inputs go in the top row, outputs (the last four wires) in the bottom
+// row and everything else one row below its deepest input.
+fn layout(wires: &[Wire]) -> Vec<(f32, f32)> {
+    let outputs = wires.len() - 4;
+    let mut rows: Vec<usize> = vec![];
+    for wire in wires[..outputs].iter() {
+        let row = wire.srcs.iter().map(|src| rows[*src] + 1).max().unwrap_or(0);
+        rows.push(row);
+    }
+    let last = rows.iter().max().unwrap_or(&0) + 1;
+    rows.resize(wires.len(), last);
+
+    let (colsz, rowsz) = (120.0, 90.0);
+    let mut positions = vec![(0.0, 0.0); wires.len()];
+    for row in 0..=last {
+        let members: Vec<usize> = (0..wires.len()).filter(|idx| rows[*idx] == row).collect();
+        let offset = (members.len() as f32 - 1.0) / 2.0;
+        for (col, idx) in members.iter().enumerate() {
+            positions[*idx] = ((col as f32 - offset) * colsz, row as f32 * rowsz);
+        }
+    }
+    positions
+}
+
+fn hexstr8(value: u8) -> String {
+    format!("0x{:02x}", value)
+}
+
+fn render_wires(title: &str, wires: &[Wire], values1: &[u8], values2: &[u8]) -> Html {
+    let positions = layout(wires);
+    let (boxw, boxh) = (104.0, 52.0);
+    let minx = positions.iter().map(|(x, _)| *x).fold(0.0, f32::min);
+    let maxx = positions.iter().map(|(x, _)| *x).fold(0.0, f32::max);
+    let maxy = positions.iter().map(|(_, y)| *y).fold(0.0, f32::max);
+    let margin = 10.0;
+    let width = maxx - minx + boxw + 2.0 * margin;
+    let height = maxy + boxh + 2.0 * margin;
+    let transform_s = format!("translate({}, {})", -minx + boxw / 2.0 + margin, margin);
+
+    let edges: Vec<Html> = wires.iter().enumerate().flat_map(|(dst, wire)| wire.srcs.iter().map(move |src| (*src, dst))).map(|(src, dst)| {
+        let (srcx, srcy) = positions[src];
+        let (dstx, dsty) = positions[dst];
+        let stroke = if values1[src] == values2[src] { "black" } else { "red" };
+        html! {
+            <path d={format!("M {},{} L {},{}", srcx, srcy + boxh, dstx, dsty)} fill="none" stroke={stroke} stroke-width="1.5" />
+        }
+    }).collect();
+
+    let boxes: Vec<Html> = wires.iter().enumerate().map(|(idx, wire)| {
+        let (x, y) = positions[idx];
+        let (value1, value2) = (values1[idx], values2[idx]);
+        let fill = if value1 == value2 { "white" } else { "#fff3a0" };
+        html! {
+            <>
+                <rect x={format!("{}", x - boxw / 2.0)} y={format!("{}", y)} width={format!("{}", boxw)} height={format!("{}", boxh)} rx="6" ry="6" fill={fill} stroke="black" />
+                <text x={format!("{}", x)} y={format!("{}", y + 10.0)} font-family="Arial" font-size="10" font-weight="bold" fill="black" text-anchor="middle" dy=".3em">{wire.label}</text>
+                <text x={format!("{}", x)} y={format!("{}", y + 22.0)} font-family="Arial" font-size="10" fill="black" text-anchor="middle" dy=".3em">{hexstr8(value1)}</text>
+                <text x={format!("{}", x)} y={format!("{}", y + 32.0)} font-family="Arial" font-size="10" fill="black" text-anchor="middle" dy=".3em">{hexstr8(value2)}</text>
+                <text x={format!("{}", x)} y={format!("{}", y + 42.0)} font-family="Arial" font-size="10" fill="red" text-anchor="middle" dy=".3em">{hexstr8(value1 ^ value2)}</text>
+            </>
+        }
+    }).collect();
+
+    html! {
+        <div>
+            <div>{title}</div>
+            <svg width={format!("{}", width as i32)} height={format!("{}", height as i32)}>
+                <g transform={transform_s}>
+                    { for edges.into_iter() }
+                    { for boxes.into_iter() }
+                </g>
+            </svg>
+        </div>
+    }
+}
+
+// subkey and value are the (value1, value2) pairs going into an F node.
+pub fn render_f(subkey: (u64, u64), value: (u64, u64)) -> Html {
+    let values1 = f_values(subkey.0 as u16, value.0 as u32);
+    let values2 = f_values(subkey.1 as u16, value.1 as u32);
+    render_wires("f(b, a)", &F_WIRES, &values1, &values2)
+}
+
+// a and b are the (value1, value2) pairs going into fk32(a, b).
+pub fn render_fk(a: (u64, u64), b: (u64, u64)) -> Html {
+    let values1 = fk_values(a.0 as u32, b.0 as u32);
+    let values2 = fk_values(a.1 as u32, b.1 as u32);
+    render_wires("fk(a, b)", &FK_WIRES, &values1, &values2)
+}
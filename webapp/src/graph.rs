@@ -5,24 +5,45 @@
  * in the process learn yew.rs.
  */
 
+use std::rc::Rc;
+
 use yew::{
+    Callback,
+    create_portal,
+    Event,
     function_component,
+    MouseEvent,
+    WheelEvent,
+    TargetCast,
     html,
     Html,
+    NodeRef,
     Properties,
     use_effect_with_deps,
-    use_state
+    use_memo,
+    use_state,
+    UseStateHandle
 };
+use wasm_bindgen::JsCast;
+use web_sys::{Element, HtmlAnchorElement, HtmlInputElement, HtmlSelectElement};
 
 use gloo_console::log;
 use gloo_net::http::Request;
+use gloo_timers::callback::Timeout;
 
 use serde::{Deserialize, Serialize};
 
+use crate::attack_panel::Recovered;
+use crate::characteristic::{check, render_summary, Characteristic};
 use crate::computation_graph;
+use crate::computation_graph::RenderOptions;
 use crate::graph_impl::GraphImpl;
+use crate::legacy;
+use crate::inspector::render_inspector;
+use crate::validate::validate;
+use crate::viewport::{render_minimap, View, VIEWPORT_HEIGHT, VIEWPORT_WIDTH};
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Node {
     pub id: u32,
     pub color: String,
@@ -37,42 +58,472 @@ pub struct Node {
 
 #[derive(Properties, Clone, PartialEq)]
 pub struct Props {
-    pub differential: u64
+    pub differential: u64,
+    pub rounds: usize,
+    // Show the key schedule for FEAL-<rounds> instead of the cipher itself
+    // (and then the differential is a difference in the master key).
+    pub schedule: bool,
+    pub options: RenderOptions,
+    pub characteristic: Option<Characteristic>,
+    // If there is one, show this instead of FEAL-<rounds>.
+    pub upload: Option<Rc<Upload>>,
+    // What the key search came up with, if it's run.
+    pub recovered: Option<Recovered>,
+}
+
+// Everything that can go wrong between the fetch and GraphImpl::new,
+// one message per problem.
+async fn fetch_graph(url: &str) -> Result<Vec<Node>, Vec<String>> {
+    let response = Request::get(url)
+        .send()
+        .await
+        .map_err(|err| vec![format!("Unable to fetch {}: {}", url, err)])?;
+    if !response.ok() {
+        return Err(vec![format!("Unable to fetch {}: {} {}", url, response.status(), response.status_text())]);
+    }
+    let text = response
+        .text()
+        .await
+        .map_err(|err| vec![format!("Unable to read {}: {}", url, err)])?;
+    parse_graph(url, &text)
+}
+
+// A graph-N.json style node list, or a legacy {edges, nodes} graph (see legacy.rs),
+// checked by validate. name is only for the messages.
+pub fn parse_graph(name: &str, text: &str) -> Result<Vec<Node>, Vec<String>> {
+    let value: serde_json::Value = serde_json::from_str(text).map_err(|err| vec![format!("Unable to parse {}: {}", name, err)])?;
+    let nodes: Vec<Node> = if legacy::is_legacy(&value) {
+        legacy::convert(value).map_err(|errors| errors.iter().map(|err| format!("{}: {}", name, err)).collect::<Vec<String>>())?
+    }
+    else {
+        serde_json::from_value(value).map_err(|err| vec![format!("Unable to parse {}: {}", name, err)])?
+    };
+    validate(&nodes).map_err(|errors| errors.iter().map(|err| format!("{}: {}", name, err)).collect::<Vec<String>>())?;
+    Ok(nodes)
+}
+
+// A graph file the user picked or dropped on the page, instead of the built in ones.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Upload {
+    pub name: String,
+    pub graph: Result<Vec<Node>, Vec<String>>,
+}
+
+// Save the rendered <svg> inside container, the same thing as
+// cargo run -- svg in the main crate, but for whatever is on screen.
+// That's the whole width x height graph, not just what's in the window.
+fn download_svg(container: &NodeRef, filename: &str, (width, height): (f32, f32)) {
+    let Some(container) = container.cast::<Element>() else { return };
+    let Ok(Some(svg)) = container.query_selector("svg") else { return };
+    let Ok(svg) = svg.clone_node_with_deep(true) else { return };
+    let Ok(svg) = svg.dyn_into::<Element>() else { return };
+    for (name, value) in [("width", format!("{}", width as i32)), ("height", format!("{}", height as i32)), ("viewBox", format!("0 0 {} {}", width, height))] {
+        let _ = svg.set_attribute(name, &value);
+    }
+    let href = format!("data:image/svg+xml;charset=utf-8,{}", js_sys::encode_uri_component(&svg.outer_html()));
+    let document = web_sys::window().unwrap().document().unwrap();
+    let anchor: HtmlAnchorElement = document.create_element("a").unwrap().dyn_into().unwrap();
+    anchor.set_href(&href);
+    anchor.set_download(filename);
+    anchor.click();
+}
+
+// Fit, zoom, jump to a round and the minimap. These go in the sidebar (see main.rs).
+fn render_navigation(graph_impl: &GraphImpl, view: &UseStateHandle<View>, round_ref: &NodeRef) -> Html {
+    let (width, height) = graph_impl.extent();
+    let onfit = {
+        let view = view.clone();
+        move |_| view.set(View::fit(width, height))
+    };
+    let zoom = |factor: f32| {
+        let view = view.clone();
+        move |_| view.set(view.zoom_at(factor, VIEWPORT_WIDTH / 2.0, VIEWPORT_HEIGHT / 2.0))
+    };
+    let rounds = graph_impl.round_nodes();
+    let positions: Vec<(f32, f32)> = rounds.iter().map(|idx| graph_impl.position(*idx)).collect();
+    let onjump = {
+        let view = view.clone();
+        let round_ref = round_ref.clone();
+        move |_| {
+            let Some(input) = round_ref.cast::<HtmlInputElement>() else { return };
+            // Rounds are numbered from 1, the way the papers do it.
+            if let Some((x, y)) = input.value().parse::<usize>().ok().and_then(|round| round.checked_sub(1)).and_then(|round| positions.get(round)) {
+                view.set(view.center_on(*x, *y));
+            }
+        }
+    };
+    let oncenter = {
+        let view = view.clone();
+        Callback::from(move |(x, y): (f32, f32)| view.set(view.center_on(x, y)))
+    };
+
+    html! {
+        <div>
+            <button onclick={onfit}>{"Fit"}</button>
+            <button onclick={zoom(1.25)}>{"+"}</button>
+            <button onclick={zoom(0.8)}>{"-"}</button>
+            <br />
+            <label>{"Round:"}</label>
+            <input ref={round_ref.clone()} type="number" min="1" max={rounds.len().to_string()} value="1" style="width: 4em;" />
+            <button onclick={onjump}>{"Go"}</button>
+            <br />
+            {render_minimap(width, height, &graph_impl.points(), view, &oncenter)}
+        </div>
+    }
+}
+
+// The choices for the inspector's number of pairs.
+const PAIRS: [usize; 5] = [1, 64, 256, 1024, 4096];
+
+const SPEEDS: [u32; 5] = [1, 2, 5, 10, 20];
+
+// The step through controls: previous/next, autoplay and its speed.
+fn render_stepper(graph_impl: &GraphImpl, steps: &[usize], stepping: &UseStateHandle<bool>, step: &UseStateHandle<usize>, playing: &UseStateHandle<bool>, speed: &UseStateHandle<u32>) -> Html {
+    let ontoggle = {
+        let stepping = stepping.clone();
+        let step = step.clone();
+        let playing = playing.clone();
+        move |event: Event| {
+            let checkbox: HtmlInputElement = event.target_unchecked_into();
+            stepping.set(checkbox.checked());
+            step.set(0);
+            playing.set(false);
+        }
+    };
+    if !**stepping {
+        return html! {
+            <label>
+                <input type="checkbox" checked={false} onchange={ontoggle} />
+                {"Step through"}
+            </label>
+        };
+    }
+
+    let current = **step;
+    let onprev = {
+        let step = step.clone();
+        move |_| step.set(current.saturating_sub(1))
+    };
+    let onnext = {
+        let step = step.clone();
+        let last = steps.len().saturating_sub(1);
+        move |_| step.set((current + 1).min(last))
+    };
+    let onplay = {
+        let now_playing = **playing;
+        let playing = playing.clone();
+        let step = step.clone();
+        let at_end = current + 1 >= steps.len();
+        move |_| {
+            // Play from the start again once we've reached the end.
+            if !now_playing && at_end {
+                step.set(0);
+            }
+            playing.set(!now_playing);
+        }
+    };
+    let onspeed = {
+        let speed = speed.clone();
+        move |event: Event| {
+            let select: HtmlSelectElement = event.target_unchecked_into();
+            if let Ok(value) = select.value().parse::<u32>() {
+                speed.set(value);
+            }
+        }
+    };
+    let status = match steps.get(current) {
+        Some(idx) => format!("Step {} of {}: {} (node {})", current + 1, steps.len(), graph_impl.label(*idx), idx),
+        None => "Nothing to step through".to_string(),
+    };
+
+    html! {
+        <div>
+            <label>
+                <input type="checkbox" checked={true} onchange={ontoggle} />
+                {"Step through"}
+            </label>
+            <button onclick={onprev} disabled={current == 0}>{"Previous"}</button>
+            <button onclick={onnext} disabled={current + 1 >= steps.len()}>{"Next"}</button>
+            <button onclick={onplay}>{if **playing { "Pause" } else { "Play" }}</button>
+            <select onchange={onspeed}>
+                { for SPEEDS.iter().map(|value| html! {
+                    <option value={value.to_string()} selected={*value == **speed}>{format!("{} steps/s", value)}</option>
+                }) }
+            </select>
+            <span>{status}</span>
+        </div>
+    }
 }
 
 #[function_component(Graph)]
 pub fn app(props: &Props) -> Html {
-    let graph = use_state(|| None::<Vec<Node>>);
+    let graph = use_state(|| None::<Result<Vec<Node>, Vec<String>>>);
+    let container: NodeRef = NodeRef::default();
+    let selected = use_state(|| None::<usize>);
+    // Stepping through: step is an index into GraphImpl::steps, speed is in steps per second.
+    let stepping = use_state(|| false);
+    let step = use_state(|| 0usize);
+    let playing = use_state(|| false);
+    let speed = use_state(|| 2u32);
+    // What part of the graph is in the window, and where a drag started (if we're dragging).
+    let view = use_state(View::default);
+    let drag = use_state(|| None::<(i32, i32, View)>);
+    let round_ref: NodeRef = NodeRef::default();
+    // How many pairs the inspector runs through the selected node (1 is just the one on screen).
+    let pairs = use_state(|| 1usize);
 
     {
         let graph = graph.clone();
-        use_effect_with_deps(move |_| {
+        let selected = selected.clone();
+        let step = step.clone();
+        let playing = playing.clone();
+        use_effect_with_deps(move |(rounds, schedule, upload)| {
             let graph = graph.clone();
-            wasm_bindgen_futures::spawn_local(async move {
-                let fetched_graph: Option<Vec<Node>> = Request::get("/graph.json")
-                    .send()
-                    .await
-                    .unwrap()
-                    .json()
-                    .await
-                    .unwrap();
-                graph.set(fetched_graph.clone());
-                log!(format!("Loaded {} nodes from graph.json.", fetched_graph.unwrap().len()));
-            });
+            // These are written by the main crate: cargo run -- graph --rounds N [--schedule]
+            let url = if *schedule { format!("/keyschedule-{}.json", rounds) } else { format!("/graph-{}.json", rounds) };
+            graph.set(None);
+            selected.set(None);
+            step.set(0);
+            playing.set(false);
+            if let Some(upload) = upload {
+                graph.set(Some(upload.graph.clone()));
+            }
+            else {
+                wasm_bindgen_futures::spawn_local(async move {
+                    let fetched_graph = fetch_graph(&url).await;
+                    match &fetched_graph {
+                        Ok(nodes) => log!(format!("Loaded {} nodes from {}.", nodes.len(), url)),
+                        Err(errors) => log!(format!("Unable to load {}: {} errors.", url, errors.len())),
+                    }
+                    graph.set(Some(fetched_graph));
+                });
+            }
             || ()
-        }, ());
+        }, (props.rounds, props.schedule, props.upload.clone()));
     }
 
+    // The plaintext and keys are random, so only draw new ones when the graph
+    // or the differential changes, not every time something is clicked.
+    let graph_impl = {
+        let graph = graph.clone();
+        let loaded = matches!(*graph, Some(Ok(_)));
+        use_memo(move |(differential, _, _, _, _)| {
+            match &*graph {
+                Some(Ok(graph_data)) => Some(GraphImpl::new(graph_data, *differential)),
+                _ => None,
+            }
+        }, (props.differential, props.rounds, props.schedule, props.upload.clone(), loaded))
+    };
 
-    if let Some(graph_data) = &*graph {
-        let graph_impl = GraphImpl::new(graph_data, props.differential);
-        html! {
-            <div>{graph_impl.render()}</div>
-        }
+    let onselect = {
+        let selected = selected.clone();
+        Callback::from(move |idx: usize| selected.set(Some(idx)))
+    };
+
+    // Running thousands of pairs isn't free, so only redo it when something changed.
+    let differences = {
+        let graph_impl = graph_impl.clone();
+        let loaded = matches!(*graph, Some(Ok(_)));
+        use_memo(move |(selected, pairs, _, _, _, _, _)| {
+            match (&*graph_impl, selected) {
+                (Some(graph_impl), Some(idx)) if *pairs > 1 => Some(graph_impl.differences(*idx, *pairs)),
+                _ => None,
+            }
+        }, (*selected, *pairs, props.differential, props.rounds, props.schedule, props.upload.clone(), loaded))
+    };
+
+    let steps = graph_impl.as_ref().as_ref().map(|graph_impl| graph_impl.steps()).unwrap_or_default();
+
+    // Start out looking at the whole thing, whenever a new graph arrives.
+    let extent = graph_impl.as_ref().as_ref().map(|graph_impl| graph_impl.extent());
+    {
+        let view = view.clone();
+        use_effect_with_deps(move |extent| {
+            if let Some((width, height)) = extent {
+                view.set(View::fit(*width, *height));
+            }
+            || ()
+        }, extent);
     }
-    else {
-        html! {
-            <div>{"Loading..."}</div>
-        }
+
+    // When stepping, keep the current node in the window.
+    {
+        let view = view.clone();
+        let graph_impl = graph_impl.clone();
+        let current = if *stepping { steps.get(*step).copied() } else { None };
+        use_effect_with_deps(move |current| {
+            if let (Some(idx), Some(graph_impl)) = (current, &*graph_impl) {
+                let (x, y) = graph_impl.position(*idx);
+                if !view.contains(x, y) {
+                    view.set(view.center_on(x, y));
+                }
+            }
+            || ()
+        }, current);
+    }
+
+    // Autoplay is a chain of timeouts, one per step, so it stops by itself at the end.
+    {
+        let deps = (*playing, *step, *speed, steps.len());
+        let step = step.clone();
+        let playing = playing.clone();
+        use_effect_with_deps(move |(now_playing, current, speed, count)| {
+            let timeout = if *now_playing && current + 1 < *count {
+                let next = current + 1;
+                Some(Timeout::new(1000 / speed, move || step.set(next)))
+            }
+            else {
+                if *now_playing {
+                    playing.set(false);
+                }
+                None
+            };
+            move || drop(timeout)
+        }, deps);
+    }
+
+    match (&*graph, &*graph_impl) {
+        (Some(Ok(_)), Some(graph_impl)) => {
+            let onclick = {
+                let container = container.clone();
+                let filename = match &props.upload {
+                    Some(upload) => format!("{}-{:016x}.svg", upload.name.trim_end_matches(".json"), props.differential),
+                    None => {
+                        let prefix = if props.schedule { "keyschedule" } else { "feal" };
+                        format!("{}{}-{:016x}.svg", prefix, props.rounds, props.differential)
+                    },
+                };
+                let extent = graph_impl.extent();
+                move |_| download_svg(&container, &filename, extent)
+            };
+            let onclose = {
+                let selected = selected.clone();
+                move |_| selected.set(None)
+            };
+            let onpairs = {
+                let pairs = pairs.clone();
+                move |event: Event| {
+                    let select: HtmlSelectElement = event.target_unchecked_into();
+                    if let Ok(value) = select.value().parse::<usize>() {
+                        pairs.set(value);
+                    }
+                }
+            };
+            let panel = selected.map(|idx| {
+                let labels = graph_impl.labels();
+                html! {
+                    <>
+                        <label>{"Pairs:"}</label>
+                        <select onchange={onpairs}>
+                            { for PAIRS.iter().map(|value| html! {
+                                <option value={value.to_string()} selected={*value == *pairs}>{value}</option>
+                            }) }
+                        </select>
+                        {render_inspector(graph_impl.node(idx), &labels[idx], &labels, graph_impl.eval(idx), (*differences).as_deref(), &onselect)}
+                        {graph_impl.detail(idx).unwrap_or_default()}
+                    </>
+                }
+            });
+            let onwheel = {
+                let view = view.clone();
+                let container = container.clone();
+                move |event: WheelEvent| {
+                    let Some(element) = container.cast::<Element>() else { return };
+                    let rect = element.get_bounding_client_rect();
+                    let (px, py) = (event.client_x() as f32 - rect.left() as f32, event.client_y() as f32 - rect.top() as f32);
+                    let factor = if event.delta_y() < 0.0 { 1.1 } else { 1.0 / 1.1 };
+                    view.set(view.zoom_at(factor, px, py));
+                }
+            };
+            let onmousedown = {
+                let view = view.clone();
+                let drag = drag.clone();
+                move |event: MouseEvent| drag.set(Some((event.client_x(), event.client_y(), *view)))
+            };
+            let onmousemove = {
+                let view = view.clone();
+                let drag = drag.clone();
+                move |event: MouseEvent| {
+                    if let Some((startx, starty, start)) = *drag {
+                        view.set(start.pan((event.client_x() - startx) as f32, (event.client_y() - starty) as f32));
+                    }
+                }
+            };
+            let onmouseup = {
+                let drag = drag.clone();
+                move |_| drag.set(None)
+            };
+            let onmouseleave = {
+                let drag = drag.clone();
+                move |_| drag.set(None)
+            };
+            let navigation = match web_sys::window().and_then(|window| window.document()).and_then(|document| document.get_element_by_id("navigation")) {
+                Some(host) => create_portal(render_navigation(graph_impl, &view, &round_ref), host),
+                None => html! {},
+            };
+            let current = steps.get(*step).copied();
+            let options = RenderOptions {step: if *stepping { current } else { None }, ..props.options};
+            let stepper = render_stepper(graph_impl, &steps, &stepping, &step, &playing, &speed);
+            let predictions = match (&*graph, &props.characteristic) {
+                (Some(Ok(graph_data)), Some(characteristic)) => Some(characteristic.resolve(graph_data)),
+                _ => None,
+            };
+            let checks = match &predictions {
+                Some(Ok(predictions)) => check(predictions, |idx| graph_impl.difference(idx)),
+                _ => vec![],
+            };
+            let summary = match &predictions {
+                Some(Ok(_)) => render_summary(&checks),
+                Some(Err(errors)) => html! {
+                    <div class="error">
+                        <div>{"The characteristic doesn't fit this graph:"}</div>
+                        <ul>
+                            { for errors.iter().map(|error| html! { <li>{error}</li> }) }
+                        </ul>
+                    </div>
+                },
+                None => html! {},
+            };
+            html! {
+                <div style="display: flex; align-items: flex-start;">
+                    <div>
+                        <button onclick={onclick}>{"Download SVG"}</button>
+                        {stepper}
+                        {summary}
+                        <div ref={container} style={if drag.is_some() { "cursor: grabbing;" } else { "cursor: grab;" }} onwheel={onwheel} onmousedown={onmousedown} onmousemove={onmousemove} onmouseup={onmouseup} onmouseleave={onmouseleave}>
+                            {graph_impl.render(&options, &onselect, &checks, props.recovered.as_ref(), &view)}
+                        </div>
+                        {navigation}
+                    </div>
+                    {
+                        if let Some(panel) = panel {
+                            html! {
+                                <div style="position: sticky; top: 0; background: white; border: 1px solid black; padding: 5px;">
+                                    <button onclick={onclose}>{"Close"}</button>
+                                    {panel}
+                                </div>
+                            }
+                        } else {
+                            html! {}
+                        }
+                    }
+                </div>
+            }
+        },
+        (Some(Err(errors)), _) => {
+            html! {
+                <div class="error">
+                    <div>{"Unable to load the graph:"}</div>
+                    <ul>
+                        { for errors.iter().map(|error| html! { <li>{error}</li> }) }
+                    </ul>
+                </div>
+            }
+        },
+        _ => {
+            html! {
+                <div>{"Loading..."}</div>
+            }
+        },
     }
 }
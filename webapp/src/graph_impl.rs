@@ -12,21 +12,32 @@ use wasm_bindgen::prelude::wasm_bindgen;
 
 use yew::{
     html,
+    Callback,
     Html
 };
 
 use crate::graph::Node;
+use crate::attack_panel::{render_recovered, Recovered};
+use crate::characteristic::{render_check, Check};
+use crate::inspector::evaluate;
+use crate::viewport::{View, VIEWPORT_HEIGHT, VIEWPORT_WIDTH};
 use crate::computation_graph::{
     ComputationGraph,
     ComputationNode,
+    RenderOptions,
     Plaintext,
-    Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8_11, Key12_15,
+    Key,
     Copy16, Copy32, Copy64,
     Left, Right,
     F,
     Xor32, Xor64,
     Swap,
     Ciphertext,
+    MasterKey,
+    Zero32,
+    Fk,
+    Left16, Right16,
+    Subkey,
 };
 
 #[wasm_bindgen]
@@ -50,8 +61,15 @@ pub fn random_u64() -> u64 {
     result
 }
 
+const MARGIN: f32 = 100.0;
+
 pub struct GraphImpl {
+    nodes: Vec<Node>,
     compnodes: Vec<Rc<RefCell<dyn ComputationNode>>>,
+    // Both values at every node, worked out once up front: render needs
+    // all of them, and going back up the graph for each one is far too
+    // slow for FEAL-16 and up.
+    values: Vec<(u64, u64)>,
 }
 
 impl GraphImpl {
@@ -59,17 +77,11 @@ impl GraphImpl {
         let mut compnodes: Vec<Rc<RefCell<dyn ComputationNode>>> = vec![];
         for node in graph_data {
             let res : Rc<RefCell<dyn ComputationNode>> = match node.compgraph {
-                ComputationGraph::Plaintext {} => Rc::new(RefCell::new(Plaintext{node: node.clone(), differential: differential, value: random_u64()})),
-                ComputationGraph::Key0 {} => Rc::new(RefCell::new(Key0{node: node.clone(), key: random_u16()})),
-                ComputationGraph::Key1 {} => Rc::new(RefCell::new(Key1{node: node.clone(), key: random_u16()})),
-                ComputationGraph::Key2 {} => Rc::new(RefCell::new(Key2{node: node.clone(), key: random_u16()})),
-                ComputationGraph::Key3 {} => Rc::new(RefCell::new(Key3{node: node.clone(), key: random_u16()})),
-                ComputationGraph::Key4 {} => Rc::new(RefCell::new(Key4{node: node.clone(), key: random_u16()})),
-                ComputationGraph::Key5 {} => Rc::new(RefCell::new(Key5{node: node.clone(), key: random_u16()})),
-                ComputationGraph::Key6 {} => Rc::new(RefCell::new(Key6{node: node.clone(), key: random_u16()})),
-                ComputationGraph::Key7 {} => Rc::new(RefCell::new(Key7{node: node.clone(), key: random_u16()})),
-                ComputationGraph::Key8_11 {} => Rc::new(RefCell::new(Key8_11{node: node.clone(), key: random_u64()})),
-                ComputationGraph::Key12_15 {} => Rc::new(RefCell::new(Key12_15{node: node.clone(), key: random_u64()})),
+                ComputationGraph::Plaintext {} => Rc::new(RefCell::new(Plaintext{node: node.clone(), differential, value: random_u64()})),
+                ComputationGraph::Key {index} => {
+                    let key = if node.bitsize == 16 { random_u16() as u64 } else { random_u64() };
+                    Rc::new(RefCell::new(Key::new(node.clone(), index, key)))
+                },
                 ComputationGraph::Copy16 {src} => Rc::new(RefCell::new(Copy16{node: node.clone(), src: compnodes[src].clone()})),
                 ComputationGraph::Copy32 {src} => Rc::new(RefCell::new(Copy32{node: node.clone(), src: compnodes[src].clone()})),
                 ComputationGraph::Copy64 {src} => Rc::new(RefCell::new(Copy64{node: node.clone(), src: compnodes[src].clone()})),
@@ -80,10 +92,17 @@ impl GraphImpl {
                 ComputationGraph::Xor64 {a, b} => Rc::new(RefCell::new(Xor64{node: node.clone(), a: compnodes[a].clone(), b: compnodes[b].clone()})),
                 ComputationGraph::Swap {left, right} => Rc::new(RefCell::new(Swap{node: node.clone(), left: compnodes[left].clone(), right: compnodes[right].clone()})),
                 ComputationGraph::Ciphertext {src} => Rc::new(RefCell::new(Ciphertext{node: node.clone(), src: compnodes[src].clone()})),
+                ComputationGraph::MasterKey {} => Rc::new(RefCell::new(MasterKey{node: node.clone(), differential, value: random_u64()})),
+                ComputationGraph::Zero32 {} => Rc::new(RefCell::new(Zero32{node: node.clone()})),
+                ComputationGraph::Fk {a, b} => Rc::new(RefCell::new(Fk{node: node.clone(), a: compnodes[a].clone(), b: compnodes[b].clone()})),
+                ComputationGraph::Left16 {src} => Rc::new(RefCell::new(Left16{node: node.clone(), src: compnodes[src].clone()})),
+                ComputationGraph::Right16 {src} => Rc::new(RefCell::new(Right16{node: node.clone(), src: compnodes[src].clone()})),
+                ComputationGraph::Subkey {src, index} => Rc::new(RefCell::new(Subkey::new(node.clone(), index, compnodes[src].clone()))),
             };
             compnodes.push(res);
         }
-        GraphImpl{compnodes: compnodes,}
+        let values = evaluate(graph_data, &|idx| compnodes[idx].borrow().source().unwrap_or_default());
+        GraphImpl{nodes: graph_data.clone(), compnodes, values}
     }
 
     pub fn compute_size(&self) -> (f32, f32, f32, f32) {
@@ -95,27 +114,152 @@ impl GraphImpl {
         (min_x, max_x, min_y, max_y)
     }
 
-    pub fn render(&self) -> Html {
+    // The size of the whole drawing, margins and all, and where the
+    // nodes' (0, 0) ends up in it.
+    pub fn extent(&self) -> (f32, f32) {
         let (minx, maxx, miny, maxy) = self.compute_size();
-        let scale = 1.0;
-        let margin = 100.0;
-        let width = maxx - minx; let width_s = format!("{}", ((width + 2.0 * margin) as i32));
-        let height = maxy - miny; let height_s = format!("{}", ((height + 100.0 + 2.0 * margin) as i32));
-        let transform_s = format!("translate({}, {}) scale({}, {})", -minx + margin, -miny + margin, scale, scale);
+        (maxx - minx + 2.0 * MARGIN, maxy - miny + 100.0 + 2.0 * MARGIN)
+    }
+
+    fn origin(&self) -> (f32, f32) {
+        let (minx, _, miny, _) = self.compute_size();
+        (-minx + MARGIN, -miny + MARGIN)
+    }
+
+    // Where node idx is drawn, in the same coordinates as extent (and View).
+    pub fn position(&self, idx: usize) -> (f32, f32) {
+        let (originx, originy) = self.origin();
+        let node = self.compnodes[idx].borrow();
+        (node.node().x + originx, node.node().y + originy)
+    }
+
+    // Every node's position and color, for the minimap.
+    pub fn points(&self) -> Vec<(f32, f32, String)> {
+        let (originx, originy) = self.origin();
+        self.compnodes.iter().map(|node| {
+            let node = node.borrow();
+            (node.node().x + originx, node.node().y + originy, node.node().color.clone())
+        }).collect()
+    }
+
+    // The F (or, in a key schedule, fk) node of each round, in order.
+    pub fn round_nodes(&self) -> Vec<usize> {
+        self.compnodes.iter().map(|node| node.borrow().node().clone())
+            .filter(|node| matches!(node.compgraph, ComputationGraph::F {..} | ComputationGraph::Fk {..}))
+            .map(|node| node.id as usize)
+            .collect()
+    }
+
+    pub fn detail(&self, idx: usize) -> Option<Html> {
+        self.compnodes.get(idx).and_then(|node| node.borrow().detail(&self.values))
+    }
+
+    // The nodes to stop at when stepping through. Copies are just forks in
+    // a wire, so they come along with whatever is next.
+    pub fn steps(&self) -> Vec<usize> {
+        self.compnodes.iter().map(|node| node.borrow().node().clone())
+            .filter(|node| !matches!(node.compgraph, ComputationGraph::Copy16 {..} | ComputationGraph::Copy32 {..} | ComputationGraph::Copy64 {..}))
+            .map(|node| node.id as usize)
+            .collect()
+    }
+
+    pub fn label(&self, idx: usize) -> String {
+        self.compnodes[idx].borrow().label().to_string()
+    }
+
+    pub fn node(&self, idx: usize) -> &Node {
+        &self.nodes[idx]
+    }
+
+    pub fn labels(&self) -> Vec<String> {
+        self.compnodes.iter().map(|node| node.borrow().label().to_string()).collect()
+    }
 
-        let node_htmls: Vec<Html> = self.compnodes.iter().map(|node| node.borrow().render_node()).collect();
-        let edge_htmls: Vec<Html> = self.compnodes.iter().map(|node| node.borrow().render_edges()).collect();
+    pub fn eval(&self, idx: usize) -> (u64, u64) {
+        self.values[idx]
+    }
+
+    // The difference at node idx for this many pairs: the keys stay the same,
+    // but each pair gets a new random plaintext (or master key) with the same
+    // differential.
+    pub fn differences(&self, idx: usize, pairs: usize) -> Vec<u64> {
+        let nodes = &self.nodes[..=idx];
+        (0..pairs).map(|_| {
+            let sources = |src: usize| {
+                let (value1, value2) = self.eval(src);
+                match self.nodes[src].compgraph {
+                    ComputationGraph::Plaintext {} | ComputationGraph::MasterKey {} => {
+                        let value = random_u64();
+                        (value, value ^ value1 ^ value2)
+                    },
+                    _ => (value1, value2),
+                }
+            };
+            let values = evaluate(nodes, &sources);
+            values[idx].0 ^ values[idx].1
+        }).collect()
+    }
+
+    pub fn difference(&self, idx: usize) -> u64 {
+        let (value1, value2) = self.values[idx];
+        value1 ^ value2
+    }
+
+    // checks are the characteristic's predictions (if there is one) to mark on top of the nodes,
+    // and recovered is what the key search found, to mark on its Key nodes.
+    // The svg is always VIEWPORT_WIDTH x VIEWPORT_HEIGHT, and view picks what's in it.
+    pub fn render(&self, options: &RenderOptions, onselect: &Callback<usize>, checks: &[Check], recovered: Option<&Recovered>, view: &View) -> Html {
+        let (originx, originy) = self.origin();
+        let width_s = format!("{}", VIEWPORT_WIDTH as i32);
+        let height_s = format!("{}", VIEWPORT_HEIGHT as i32);
+        let transform_s = format!("translate({}, {})", originx, originy);
+
+        // The size stays that of the whole graph, so nothing jumps around while stepping.
+        let last = options.step.unwrap_or(self.compnodes.len());
+        let shown = &self.compnodes[..self.compnodes.len().min(last + 1)];
+        let node_htmls: Vec<Html> = shown.iter().map(|node| node.borrow().render_node(&self.values, options, onselect)).collect();
+        let edge_htmls: Vec<Html> = shown.iter().map(|node| node.borrow().render_edges(&self.values, options)).collect();
+        let check_htmls: Vec<Html> = checks.iter().filter(|check| check.id <= last).map(|check| render_check(self.compnodes[check.id].borrow().node(), check)).collect();
+        let recovered_htmls: Vec<Html> = match recovered {
+            Some(recovered) => shown.iter().filter_map(|node| {
+                let node = node.borrow();
+                match node.node().compgraph {
+                    ComputationGraph::Key {index} if recovered.indices.contains(&index) => Some(render_recovered(node.node(), recovered)),
+                    _ => None,
+                }
+            }).collect(),
+            None => vec![],
+        };
+        let current_html = match options.step.and_then(|idx| self.compnodes.get(idx)) {
+            Some(current) => {
+                let current = current.borrow();
+                let node = current.node();
+                html! {
+                    <circle cx={format!("{}", node.x)} cy={format!("{}", node.y)} r={format!("{}", node.radius + 12.0)} fill="none" stroke="gold" stroke-width="4" pointer-events="none" />
+                }
+            },
+            None => html! {},
+        };
 
         html! {
-                <svg width={width_s} height={height_s} style="margin-right: 10px;">
+                <svg xmlns="http://www.w3.org/2000/svg" width={width_s} height={height_s} viewBox={view.view_box()} style="margin-right: 10px; border: 1px solid #d3d3d3;">
                     <defs>
                         <marker id="arrowhead" markerWidth="10" markerHeight="7" refX="0" refY="3.5" orient="auto">
                             <polygon points="0 0, 10 3.5, 0 7" />
                         </marker>
+                        <marker id="arrowhead-zero" markerWidth="10" markerHeight="7" refX="0" refY="3.5" orient="auto">
+                            <polygon points="0 0, 10 3.5, 0 7" fill="#bbbbbb" />
+                        </marker>
+                        <marker id="arrowhead-active" markerWidth="10" markerHeight="7" refX="0" refY="3.5" orient="auto">
+                            <polygon points="0 0, 10 3.5, 0 7" fill="red" />
+                        </marker>
                     </defs>
                     <g transform={transform_s}>
                         { for edge_htmls.into_iter() }
                         { for node_htmls.into_iter() }
+                        { for check_htmls.into_iter() }
+                        { for recovered_htmls.into_iter() }
+                        {current_html}
                     </g>
                 </svg>
         }
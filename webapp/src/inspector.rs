@@ -0,0 +1,143 @@
+// vim: expandtab shiftwidth=4 tabstop=4:
+
+/* Everything about the selected node: what it is, what feeds it, both
+ * values and their difference (in hex and binary), and, when we're
+ * running many pairs, which differences showed up there and how often.
+ */
+
+use std::collections::HashMap;
+
+use yew::{
+    html,
+    Callback,
+    Html
+};
+
+use crate::computation_graph::{hexstr, ComputationGraph};
+use crate::feal::{f, fk32};
+use crate::graph::Node;
+
+// One pass over the graph in node order, with sources giving the values of
+// the nodes without inputs (plaintext, keys, ...). This is the same as
+// graph::eval in the main crate, and it's the only evaluator here: GraphImpl
+// runs it once for what it draws, and again for each pair in differences.
+// Nothing goes back up the graph, so it's cheap enough for thousands of pairs.
+pub fn evaluate(nodes: &[Node], sources: &dyn Fn(usize) -> (u64, u64)) -> Vec<(u64, u64)> {
+    let mut values: Vec<(u64, u64)> = Vec::with_capacity(nodes.len());
+    for (idx, node) in nodes.iter().enumerate() {
+        let value = match node.compgraph {
+            ComputationGraph::Plaintext {} | ComputationGraph::Key {..} | ComputationGraph::MasterKey {} | ComputationGraph::Zero32 {} => sources(idx),
+            ComputationGraph::Copy16 {src} => (values[src].0 & 0x00ffff, values[src].1 & 0x00ffff),
+            ComputationGraph::Copy32 {src} => (values[src].0 & 0x00ffffffff, values[src].1 & 0x00ffffffff),
+            ComputationGraph::Copy64 {src} => values[src],
+            ComputationGraph::Left {src} => ((values[src].0 >> 32) & 0x00ffffffff, (values[src].1 >> 32) & 0x00ffffffff),
+            ComputationGraph::Right {src} => (values[src].0 & 0x00ffffffff, values[src].1 & 0x00ffffffff),
+            ComputationGraph::F {subkey, value} => {
+                let output1 = f(values[subkey].0 as u16, values[value].0 as u32);
+                let output2 = f(values[subkey].1 as u16, values[value].1 as u32);
+                (output1.into(), output2.into())
+            },
+            ComputationGraph::Xor32 {a, b} => ((values[a].0 ^ values[b].0) & 0x00ffffffff, (values[a].1 ^ values[b].1) & 0x00ffffffff),
+            ComputationGraph::Xor64 {a, b} => (values[a].0 ^ values[b].0, values[a].1 ^ values[b].1),
+            ComputationGraph::Swap {left, right} => {
+                let (left1, left2) = (values[left].0 & 0x00ffffffff, values[left].1 & 0x00ffffffff);
+                let (right1, right2) = (values[right].0 & 0x00ffffffff, values[right].1 & 0x00ffffffff);
+                (((right1 << 32) | left1), ((right2 << 32) | left2))
+            },
+            ComputationGraph::Ciphertext {src} => values[src],
+            ComputationGraph::Fk {a, b} => {
+                let output1 = fk32(values[a].0 as u32, values[b].0 as u32);
+                let output2 = fk32(values[a].1 as u32, values[b].1 as u32);
+                (output1.into(), output2.into())
+            },
+            ComputationGraph::Left16 {src} => ((values[src].0 >> 16) & 0x00ffff, (values[src].1 >> 16) & 0x00ffff),
+            ComputationGraph::Right16 {src} => (values[src].0 & 0x00ffff, values[src].1 & 0x00ffff),
+            ComputationGraph::Subkey {src, ..} => values[src],
+        };
+        values.push(value);
+    }
+    values
+}
+
+// Binary, a space between bytes.
+fn binstr(value: u64, bitsize: u32) -> String {
+    let bits = format!("{:0width$b}", value, width = bitsize as usize);
+    bits.as_bytes().chunks(8).map(|byte| String::from_utf8_lossy(byte).to_string()).collect::<Vec<String>>().join(" ")
+}
+
+// At most this many rows of the distribution.
+const TOP: usize = 16;
+
+fn render_distribution(node: &Node, differences: &[u64]) -> Html {
+    let mut counts: HashMap<u64, usize> = HashMap::new();
+    for difference in differences {
+        *counts.entry(*difference).or_insert(0) += 1;
+    }
+    let mut counts: Vec<(u64, usize)> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    let total = differences.len() as f64;
+
+    html! {
+        <div>
+            <div>{format!("{} distinct differences over {} pairs:", counts.len(), differences.len())}</div>
+            <table style="font-family: monospace;">
+                <tr><th>{"difference"}</th><th>{"count"}</th><th>{"fraction"}</th></tr>
+                { for counts.iter().take(TOP).map(|(difference, count)| html! {
+                    <tr>
+                        <td>{hexstr(*difference, node.bitsize)}</td>
+                        <td>{count}</td>
+                        <td>{format!("{:.4}", *count as f64 / total)}</td>
+                    </tr>
+                }) }
+            </table>
+            {
+                if counts.len() > TOP {
+                    html! { <div>{format!("... and {} more", counts.len() - TOP)}</div> }
+                } else {
+                    html! {}
+                }
+            }
+        </div>
+    }
+}
+
+// labels are the labels of all the nodes (for naming the inputs), and clicking
+// an input selects it. differences are the ones seen at this node over many
+// pairs, if we're doing that.
+pub fn render_inspector(node: &Node, label: &str, labels: &[String], (value1, value2): (u64, u64), differences: Option<&[u64]>, onselect: &Callback<usize>) -> Html {
+    let difference = value1 ^ value2;
+    let inputs: Vec<Html> = node.compgraph.inputs().into_iter().map(|(name, src)| {
+        let onclick = {
+            let onselect = onselect.clone();
+            move |_| onselect.emit(src)
+        };
+        html! {
+            <li>{format!("{}: ", name)}<a style="cursor: pointer; color: blue; text-decoration: underline;" onclick={onclick}>{format!("{} (node {})", labels[src], src)}</a></li>
+        }
+    }).collect();
+
+    html! {
+        <div>
+            <div><b>{label}</b>{format!(" (node {}, {} bits)", node.id, node.bitsize)}</div>
+            {
+                if inputs.is_empty() {
+                    html! { <div>{"No inputs"}</div> }
+                } else {
+                    html! { <ul>{ for inputs.into_iter() }</ul> }
+                }
+            }
+            <table style="font-family: monospace;">
+                <tr><td>{"value1"}</td><td>{hexstr(value1, node.bitsize)}</td><td>{binstr(value1, node.bitsize)}</td></tr>
+                <tr><td>{"value2"}</td><td>{hexstr(value2, node.bitsize)}</td><td>{binstr(value2, node.bitsize)}</td></tr>
+                <tr style="color: red;"><td>{"difference"}</td><td>{hexstr(difference, node.bitsize)}</td><td>{binstr(difference, node.bitsize)}</td></tr>
+            </table>
+            <div>{format!("Hamming weight of the difference: {}", difference.count_ones())}</div>
+            {
+                match differences {
+                    Some(differences) => render_distribution(node, differences),
+                    None => html! {},
+                }
+            }
+        </div>
+    }
+}
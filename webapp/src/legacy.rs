@@ -0,0 +1,132 @@
+// vim: expandtab shiftwidth=4 tabstop=4:
+
+/* The graphs graph/graph.py used to write (static/feal.json, static/dummy.json,
+ * the Graph struct in pristine.rs) are {"edges": [...], "nodes": {id: node}},
+ * with the inputs on the edges and labels like copy, xor, key3 and key8_11.
+ * Turn those into the node list we use now: each node becomes the same JSON
+ * object it would be in a graph-N.json, and serde takes it from there.
+ */
+
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use crate::graph::Node;
+
+#[derive(Debug, Deserialize)]
+struct Edge {
+    src: u32,
+    dst: u32,
+    label: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct LegacyNode {
+    color: String,
+    x: f32,
+    y: f32,
+    radius: f32,
+    size: f32,
+    bitsize: u32,
+    label: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct LegacyGraph {
+    edges: Vec<Edge>,
+    nodes: HashMap<u32, LegacyNode>,
+}
+
+pub fn is_legacy(value: &Value) -> bool {
+    value.get("edges").is_some() && value.get("nodes").is_some()
+}
+
+// The current label, and the key index if it's a key. copy and xor didn't
+// say how wide they were, but the node's bitsize does.
+fn translate_label(label: &str, bitsize: u32) -> (String, Option<usize>) {
+    match label {
+        "copy" | "xor" => (format!("{}{}", label, bitsize), None),
+        _ => {
+            let index = label.strip_prefix("key").and_then(|rest| rest.split(['_', '-']).next()).and_then(|first| first.parse::<usize>().ok());
+            match index {
+                Some(index) => ("key".to_string(), Some(index)),
+                None => (label.to_string(), None),
+            }
+        },
+    }
+}
+
+pub fn convert(value: Value) -> Result<Vec<Node>, Vec<String>> {
+    let legacy: LegacyGraph = serde_json::from_value(value).map_err(|err| vec![format!("not a legacy graph either: {}", err)])?;
+
+    // The ids were never guaranteed to be 0..n, so renumber them in order.
+    let mut ids: Vec<u32> = legacy.nodes.keys().copied().collect();
+    ids.sort();
+    let position: HashMap<u32, usize> = ids.iter().enumerate().map(|(position, id)| (*id, position)).collect();
+
+    let mut errors = vec![];
+    let mut inputs: HashMap<u32, Vec<(String, usize)>> = HashMap::new();
+    for edge in legacy.edges.iter() {
+        match (position.get(&edge.src), position.contains_key(&edge.dst)) {
+            (Some(src), true) => inputs.entry(edge.dst).or_default().push((edge.label.clone(), *src)),
+            _ => errors.push(format!("the edge {} -> {} refers to a node that isn't there", edge.src, edge.dst)),
+        }
+    }
+
+    let mut nodes = vec![];
+    for (idx, id) in ids.iter().enumerate() {
+        let node = &legacy.nodes[id];
+        let (label, index) = translate_label(&node.label, node.bitsize);
+        let mut object: Map<String, Value> = Map::new();
+        object.insert("id".to_string(), json!(idx));
+        object.insert("color".to_string(), json!(node.color));
+        object.insert("x".to_string(), json!(node.x));
+        object.insert("y".to_string(), json!(node.y));
+        object.insert("radius".to_string(), json!(node.radius));
+        object.insert("size".to_string(), json!(node.size));
+        object.insert("bitsize".to_string(), json!(node.bitsize));
+        object.insert("label".to_string(), json!(label));
+        if let Some(index) = index {
+            object.insert("index".to_string(), json!(index));
+        }
+        for (name, src) in inputs.remove(id).unwrap_or_default() {
+            object.insert(name, json!(src));
+        }
+        match serde_json::from_value::<Node>(Value::Object(object)) {
+            Ok(converted) => nodes.push(converted),
+            Err(err) => errors.push(format!("node {} ({}): {}", id, node.label, err)),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(nodes)
+    }
+    else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validate::validate;
+
+    #[test]
+    fn converts_the_old_static_graphs() {
+        for (text, count) in [(include_str!("../static/feal.json"), 94), (include_str!("../static/dummy.json"), 2)] {
+            let value: Value = serde_json::from_str(text).unwrap();
+            assert!(is_legacy(&value));
+            let nodes = convert(value).unwrap();
+            assert_eq!(nodes.len(), count);
+            assert_eq!(validate(&nodes), Ok(()));
+        }
+    }
+
+    #[test]
+    fn missing_nodes() {
+        let value = json!({"edges": [{"src": 0, "dst": 5, "label": "src"}], "nodes": {}});
+        assert_eq!(convert(value).unwrap_err(), vec!["the edge 0 -> 5 refers to a node that isn't there".to_string()]);
+        assert!(!is_legacy(&json!([])));
+    }
+}
@@ -5,15 +5,23 @@
  * in the process learn yew.rs.
  */
 
+use std::rc::Rc;
+
+use gloo_events::EventListener;
 use yew::{
+    DragEvent,
     function_component,
     html,
+    Event,
     Html,
     KeyboardEvent,
     NodeRef,
-    use_state
+    TargetCast,
+    use_effect_with_deps,
+    use_state,
+    UseStateHandle
 };
-use web_sys::HtmlInputElement;
+use web_sys::{HtmlInputElement, HtmlSelectElement, HtmlTextAreaElement};
 
 // use gloo_console::log;
 // use wasm_bindgen::JsValue;
@@ -22,13 +30,90 @@ pub mod feal;
 pub mod graph;
 pub mod graph_impl;
 pub mod computation_graph;
+pub mod f_detail;
+pub mod validate;
+pub mod characteristic;
+pub mod viewport;
+pub mod inspector;
+pub mod legacy;
+pub mod attack;
+pub mod attack_panel;
+pub mod ddt;
+
+use attack_panel::{AttackPanel, Recovered};
+use characteristic::Characteristic;
+use graph::{parse_graph, Upload};
+use computation_graph::RenderOptions;
+
+
+// There's a graph-N.json and a keyschedule-N.json in static/ for each of these.
+const ROUND_COUNTS: [usize; 4] = [4, 8, 16, 32];
+
+// Which page we're on, from the # part of the URL, so the back button works.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Route {
+    Graph,
+    Ddt,
+}
+
+impl Route {
+    fn current() -> Route {
+        let hash = web_sys::window().and_then(|window| window.location().hash().ok()).unwrap_or_default();
+        match hash.as_str() {
+            "#ddt" => Route::Ddt,
+            _ => Route::Graph,
+        }
+    }
+
+    fn hash(&self) -> &'static str {
+        match self {
+            Route::Graph => "#graph",
+            Route::Ddt => "#ddt",
+        }
+    }
 
+    fn go(&self) {
+        if let Some(window) = web_sys::window() {
+            let _ = window.location().set_hash(self.hash());
+        }
+    }
+}
+
+// Read a graph file the user handed us, in either format.
+fn load_file(file: web_sys::File, upload: UseStateHandle<Option<Rc<Upload>>>) {
+    let name = file.name();
+    let file = gloo_file::File::from(file);
+    wasm_bindgen_futures::spawn_local(async move {
+        let graph = match gloo_file::futures::read_as_text(&file).await {
+            Ok(text) => parse_graph(&name, &text),
+            Err(err) => Err(vec![format!("Unable to read {}: {}", name, err)]),
+        };
+        upload.set(Some(Rc::new(Upload {name, graph})));
+    });
+}
 
 #[function_component(App)]
 fn app() -> Html {
     let differential = use_state(|| 0u64);
+    let rounds = use_state(|| 8usize);
+    let schedule = use_state(|| false);
+    let options = use_state(RenderOptions::default);
     let error = use_state(|| None::<String>);
     let input_ref: NodeRef = NodeRef::default();
+    let characteristic = use_state(|| None::<Characteristic>);
+    let characteristic_error = use_state(|| None::<String>);
+    let characteristic_ref: NodeRef = NodeRef::default();
+    let upload = use_state(|| None::<Rc<Upload>>);
+    let recovered = use_state(|| None::<Recovered>);
+    let route = use_state(Route::current);
+
+    {
+        let route = route.clone();
+        use_effect_with_deps(move |_| {
+            let listener = web_sys::window().map(|window| EventListener::new(&window, "hashchange", move |_| route.set(Route::current())));
+            move || drop(listener)
+        }, ());
+    }
 
     let handle_click = {
         let differential = differential.clone();
@@ -59,23 +144,183 @@ fn app() -> Html {
         }
     };
 
+    let onchange = {
+        let rounds = rounds.clone();
+        move |event: Event| {
+            let select: HtmlSelectElement = event.target_unchecked_into();
+            if let Ok(num) = select.value().parse::<usize>() {
+                rounds.set(num);
+            }
+        }
+    };
+
+    let onbits = {
+        let options = options.clone();
+        move |event: Event| {
+            let checkbox: HtmlInputElement = event.target_unchecked_into();
+            options.set(RenderOptions{bits: checkbox.checked(), ..*options});
+        }
+    };
+
+    let onschedule = {
+        let schedule = schedule.clone();
+        move |event: Event| {
+            let checkbox: HtmlInputElement = event.target_unchecked_into();
+            schedule.set(checkbox.checked());
+        }
+    };
+
+    let onfile = {
+        let upload = upload.clone();
+        move |event: Event| {
+            let input: HtmlInputElement = event.target_unchecked_into();
+            if let Some(file) = input.files().and_then(|files| files.get(0)) {
+                load_file(file, upload.clone());
+            }
+        }
+    };
+
+    // The browser only lets us drop if dragover is cancelled.
+    let ondragover = |event: DragEvent| event.prevent_default();
+
+    let ondrop = {
+        let upload = upload.clone();
+        move |event: DragEvent| {
+            event.prevent_default();
+            if let Some(file) = event.data_transfer().and_then(|transfer| transfer.files()).and_then(|files| files.get(0)) {
+                load_file(file, upload.clone());
+            }
+        }
+    };
+
+    let onbuiltin = {
+        let upload = upload.clone();
+        move |_| upload.set(None)
+    };
+
+    let onload = {
+        let characteristic = characteristic.clone();
+        let characteristic_error = characteristic_error.clone();
+        let characteristic_ref = characteristic_ref.clone();
+        move |_| {
+            let textarea: HtmlTextAreaElement = characteristic_ref.cast().unwrap();
+            match Characteristic::parse(&textarea.value()) {
+                Ok(parsed) => {
+                    characteristic.set(Some(parsed));
+                    characteristic_error.set(None);
+                },
+                Err(err) => characteristic_error.set(Some(err)),
+            }
+        }
+    };
+
+    let onclear = {
+        let characteristic = characteristic.clone();
+        let characteristic_error = characteristic_error.clone();
+        move |_| {
+            characteristic.set(None);
+            characteristic_error.set(None);
+        }
+    };
+
+    let onrecovered = {
+        let recovered = recovered.clone();
+        move |found: Option<Recovered>| recovered.set(found)
+    };
+
+    // A cell of the difference distribution table was clicked.
+    let onddt = {
+        let differential = differential.clone();
+        let schedule = schedule.clone();
+        let error = error.clone();
+        move |value: u64| {
+            differential.set(value);
+            schedule.set(false);
+            error.set(None);
+            Route::Graph.go();
+        }
+    };
+
+    let page = match *route {
+        Route::Graph => html! {
+            <div style="display: flex; align-items: flex-start; position: relative">
+                <div>
+                    <graph::Graph differential={*differential} rounds={*rounds} schedule={*schedule} options={*options} characteristic={(*characteristic).clone()} upload={(*upload).clone()} recovered={(*recovered).clone()} />
+                </div>
+                <div style="position: sticky; top: 0;">
+                    <div style="border: 2px dashed #d3d3d3; padding: 5px;" ondragover={ondragover} ondrop={ondrop}>
+                        <div>{"Drop a graph JSON here, or"}</div>
+                        <input type="file" accept=".json,application/json" onchange={onfile} />
+                        {
+                            if let Some(upload) = &*upload {
+                                html! {
+                                    <div>
+                                        {format!("Showing {} ", upload.name)}
+                                        <button onclick={onbuiltin}>{"Back to FEAL-N"}</button>
+                                    </div>
+                                }
+                            } else {
+                                html! {}
+                            }
+                        }
+                    </div>
+                    <label>{"Cipher:"}</label>
+                    <select onchange={onchange}>
+                        { for ROUND_COUNTS.iter().map(|count| html! {
+                            <option value={count.to_string()} selected={*count == *rounds}>{format!("FEAL-{}", count)}</option>
+                        }) }
+                    </select>
+                    <br />
+                    <label>
+                        <input type="checkbox" checked={*schedule} onchange={onschedule} />
+                        {"Key schedule"}
+                    </label>
+                    <br />
+                    <label>
+                        <input type="checkbox" checked={options.bits} onchange={onbits} />
+                        {"Bit view"}
+                    </label>
+                    <br />
+                    <label>{if *schedule { "Key differential 0x:" } else { "Differential 0x:" }}</label>
+                    <input ref={input_ref.clone()} type="text" placeholder="Enter text" value={format!("{:x}", *differential)} onkeydown={onkeydown} />
+                    {
+                        if let Some(error_msg) = &*error {
+                            html! { <div class="error">{error_msg}</div> }
+                        } else {
+                            html! {}
+                        }
+                    }
+                    <br />
+                    // The graph puts its fit/zoom/round buttons and minimap in here.
+                    <div id="navigation"></div>
+                    <label>{"Characteristic (JSON):"}</label>
+                    <br />
+                    <textarea ref={characteristic_ref.clone()} rows="10" cols="40" placeholder={r#"{"rounds": [{"input": "0x80800000", "output": "0x02000000", "probability": 1.0}]}"#} />
+                    <br />
+                    <button onclick={onload}>{"Load"}</button>
+                    <button onclick={onclear}>{"Clear"}</button>
+                    {
+                        if let Some(error_msg) = &*characteristic_error {
+                            html! { <div class="error">{error_msg}</div> }
+                        } else {
+                            html! {}
+                        }
+                    }
+                    <AttackPanel available={*rounds == 4 && !*schedule && upload.is_none()} onrecovered={onrecovered} />
+                </div>
+            </div>
+        },
+        Route::Ddt => html! { <ddt::Ddt onselect={onddt} /> },
+    };
 
     html! {
-        <div style="display: flex; align-items: flex-start; position: relative">
+        <div>
             <div>
-                <graph::Graph differential={*differential} />
-            </div>
-            <div style="position: sticky; top: 0;">
-                <label>{"Differential 0x:"}</label>
-                <input ref={input_ref.clone()} type="text" placeholder="Enter text" onkeydown={onkeydown} />
-                {
-                    if let Some(error_msg) = &*error {
-                        html! { <div class="error">{error_msg}</div> }
-                    } else {
-                        html! {}
-                    }
-                }
+                <a href={Route::Graph.hash()}>{"Graph"}</a>
+                {" | "}
+                <a href={Route::Ddt.hash()}>{"g0/g1 difference distribution"}</a>
             </div>
+            {page}
         </div>
     }
 }
@@ -0,0 +1,199 @@
+// vim: expandtab shiftwidth=4 tabstop=4:
+
+/* GraphImpl::new assumes the graph is topologically ordered and
+ * that every src/a/b/subkey/value/left/right index points at an earlier
+ * node of the right bitsize. If that's not true it just panics inside
+ * wasm, so check all of that up front and say what's wrong instead.
+ */
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::computation_graph::ComputationGraph;
+use crate::graph::Node;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum GraphError {
+    // The graph has no nodes at all.
+    Empty,
+    // Two nodes claim the same id.
+    DuplicateId {id: u32, first: usize, second: usize},
+    // The indices are positions in the node list, so id has to match.
+    IdMismatch {position: usize, id: u32},
+    // An input points past the end of the node list.
+    OutOfRange {position: usize, input: &'static str, src: usize, count: usize},
+    // An input points at this node, or at a later one.
+    ForwardReference {position: usize, input: &'static str, src: usize},
+    // An input has the wrong bitsize for its port (e.g. a 64 bit key into an F subkey).
+    InputBitsize {position: usize, input: &'static str, src: usize, expected: u32, found: u32},
+    // The node itself has a bitsize its type can't produce.
+    NodeBitsize {position: usize, label: &'static str, expected: Vec<u32>, found: u32},
+    // There's nothing to read the result from (a ciphertext, or the
+    // subkeys of a key schedule).
+    MissingOutput,
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GraphError::Empty => write!(f, "the graph has no nodes"),
+            GraphError::DuplicateId {id, first, second} => write!(f, "nodes {} and {} both have id {}", first, second, id),
+            GraphError::IdMismatch {position, id} => write!(f, "node {} has id {} (ids must match their position)", position, id),
+            GraphError::OutOfRange {position, input, src, count} => write!(f, "node {}: {}={} is out of range (there are {} nodes)", position, input, src, count),
+            GraphError::ForwardReference {position, input, src} => write!(f, "node {}: {}={} is not an earlier node (the graph must be topologically ordered)", position, input, src),
+            GraphError::InputBitsize {position, input, src, expected, found} => write!(f, "node {}: {}={} is {} bits, expected {}", position, input, src, found, expected),
+            GraphError::NodeBitsize {position, label, expected, found} => write!(f, "node {}: a {} node can't be {} bits (expected {:?})", position, label, found, expected),
+            GraphError::MissingOutput => write!(f, "there is no ciphertext (or subkey) node"),
+        }
+    }
+}
+
+// (input name, node index, expected bitsize)
+type Port = (&'static str, usize, u32);
+
+// The label, the bitsizes the node itself may have, and the ports of its inputs.
+fn signature(compgraph: &ComputationGraph) -> (&'static str, Vec<u32>, Vec<Port>) {
+    match *compgraph {
+        ComputationGraph::Plaintext {} => ("plaintext", vec![64], vec![]),
+        ComputationGraph::Key {..} => ("key", vec![16, 64], vec![]),
+        ComputationGraph::Copy16 {src} => ("copy16", vec![16], vec![("src", src, 16)]),
+        ComputationGraph::Copy32 {src} => ("copy32", vec![32], vec![("src", src, 32)]),
+        ComputationGraph::Copy64 {src} => ("copy64", vec![64], vec![("src", src, 64)]),
+        ComputationGraph::Left {src} => ("left", vec![32], vec![("src", src, 64)]),
+        ComputationGraph::Right {src} => ("right", vec![32], vec![("src", src, 64)]),
+        ComputationGraph::F {subkey, value} => ("F", vec![32], vec![("subkey", subkey, 16), ("value", value, 32)]),
+        ComputationGraph::Xor32 {a, b} => ("xor32", vec![32], vec![("a", a, 32), ("b", b, 32)]),
+        ComputationGraph::Xor64 {a, b} => ("xor64", vec![64], vec![("a", a, 64), ("b", b, 64)]),
+        ComputationGraph::Swap {left, right} => ("swap", vec![64], vec![("left", left, 32), ("right", right, 32)]),
+        ComputationGraph::Ciphertext {src} => ("ciphertext", vec![64], vec![("src", src, 64)]),
+        ComputationGraph::MasterKey {} => ("masterkey", vec![64], vec![]),
+        ComputationGraph::Zero32 {} => ("zero32", vec![32], vec![]),
+        ComputationGraph::Fk {a, b} => ("fk", vec![32], vec![("a", a, 32), ("b", b, 32)]),
+        ComputationGraph::Left16 {src} => ("left16", vec![16], vec![("src", src, 32)]),
+        ComputationGraph::Right16 {src} => ("right16", vec![16], vec![("src", src, 32)]),
+        ComputationGraph::Subkey {src, ..} => ("subkey", vec![16], vec![("src", src, 16)]),
+    }
+}
+
+pub fn validate(nodes: &[Node]) -> Result<(), Vec<GraphError>> {
+    let mut errors = vec![];
+
+    if nodes.is_empty() {
+        return Err(vec![GraphError::Empty]);
+    }
+
+    let mut seen: HashMap<u32, usize> = HashMap::new();
+    for (position, node) in nodes.iter().enumerate() {
+        if let Some(first) = seen.insert(node.id, position) {
+            errors.push(GraphError::DuplicateId {id: node.id, first, second: position});
+        }
+        else if node.id as usize != position {
+            errors.push(GraphError::IdMismatch {position, id: node.id});
+        }
+
+        let (label, bitsizes, inputs) = signature(&node.compgraph);
+        if !bitsizes.contains(&node.bitsize) {
+            errors.push(GraphError::NodeBitsize {position, label, expected: bitsizes, found: node.bitsize});
+        }
+
+        for (input, src, expected) in inputs {
+            if src >= nodes.len() {
+                errors.push(GraphError::OutOfRange {position, input, src, count: nodes.len()});
+            }
+            else if src >= position {
+                errors.push(GraphError::ForwardReference {position, input, src});
+            }
+            else if nodes[src].bitsize != expected {
+                errors.push(GraphError::InputBitsize {position, input, src, expected, found: nodes[src].bitsize});
+            }
+        }
+    }
+
+    if !nodes.iter().any(|node| matches!(node.compgraph, ComputationGraph::Ciphertext {..} | ComputationGraph::Subkey {..})) {
+        errors.push(GraphError::MissingOutput);
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    }
+    else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(id: u32, bitsize: u32, compgraph: ComputationGraph) -> Node {
+        Node {id, color: "#ffffff".to_string(), x: 0.0, y: 0.0, radius: 20.0, size: 48.0, bitsize, compgraph}
+    }
+
+    // plaintext ^ key, and that's the ciphertext.
+    fn tiny() -> Vec<Node> {
+        vec![
+            node(0, 64, ComputationGraph::Plaintext {}),
+            node(1, 64, ComputationGraph::Key {index: 0}),
+            node(2, 64, ComputationGraph::Xor64 {a: 0, b: 1}),
+            node(3, 64, ComputationGraph::Ciphertext {src: 2}),
+        ]
+    }
+
+    fn errors(nodes: &[Node]) -> Vec<GraphError> {
+        validate(nodes).unwrap_err()
+    }
+
+    #[test]
+    fn static_graphs_pass() {
+        for (name, text) in [
+            ("graph-4", include_str!("../static/graph-4.json")),
+            ("graph-8", include_str!("../static/graph-8.json")),
+            ("graph-16", include_str!("../static/graph-16.json")),
+            ("graph-32", include_str!("../static/graph-32.json")),
+            ("keyschedule-4", include_str!("../static/keyschedule-4.json")),
+            ("keyschedule-8", include_str!("../static/keyschedule-8.json")),
+            ("keyschedule-16", include_str!("../static/keyschedule-16.json")),
+            ("keyschedule-32", include_str!("../static/keyschedule-32.json")),
+        ] {
+            let nodes: Vec<Node> = serde_json::from_str(text).unwrap();
+            assert_eq!(validate(&nodes), Ok(()), "{}", name);
+        }
+        assert_eq!(validate(&tiny()), Ok(()));
+    }
+
+    #[test]
+    fn every_error() {
+        assert_eq!(errors(&[]), vec![GraphError::Empty]);
+
+        let mut nodes = tiny();
+        nodes[2].id = 1;
+        assert_eq!(errors(&nodes), vec![GraphError::DuplicateId {id: 1, first: 1, second: 2}]);
+
+        let mut nodes = tiny();
+        nodes[3].id = 7;
+        assert_eq!(errors(&nodes), vec![GraphError::IdMismatch {position: 3, id: 7}]);
+
+        let mut nodes = tiny();
+        nodes[3].compgraph = ComputationGraph::Ciphertext {src: 9};
+        assert_eq!(errors(&nodes), vec![GraphError::OutOfRange {position: 3, input: "src", src: 9, count: 4}]);
+
+        let mut nodes = tiny();
+        nodes[2].compgraph = ComputationGraph::Xor64 {a: 0, b: 3};
+        assert_eq!(errors(&nodes), vec![GraphError::ForwardReference {position: 2, input: "b", src: 3}]);
+
+        let mut nodes = tiny();
+        nodes[1].bitsize = 16;
+        assert_eq!(errors(&nodes), vec![GraphError::InputBitsize {position: 2, input: "b", src: 1, expected: 64, found: 16}]);
+
+        let mut nodes = tiny();
+        nodes[0].bitsize = 32;
+        assert_eq!(errors(&nodes), vec![
+            GraphError::NodeBitsize {position: 0, label: "plaintext", expected: vec![64], found: 32},
+            GraphError::InputBitsize {position: 2, input: "a", src: 0, expected: 64, found: 32},
+        ]);
+
+        let mut nodes = tiny();
+        nodes.pop();
+        assert_eq!(errors(&nodes), vec![GraphError::MissingOutput]);
+    }
+}
@@ -0,0 +1,93 @@
+// vim: expandtab shiftwidth=4 tabstop=4:
+
+/* The graph is drawn into a fixed size window, and View says which part
+ * of it we're looking at: (x, y) is the top left corner in graph (svg)
+ * coordinates and zoom is screen pixels per graph pixel. FEAL-32 is
+ * way too tall to just scroll through.
+ */
+
+use wasm_bindgen::JsCast;
+use web_sys::Element;
+use yew::{
+    html,
+    Callback,
+    Html,
+    MouseEvent
+};
+
+pub const VIEWPORT_WIDTH: f32 = 900.0;
+pub const VIEWPORT_HEIGHT: f32 = 800.0;
+const MIN_ZOOM: f32 = 0.02;
+const MAX_ZOOM: f32 = 8.0;
+const MINIMAP_WIDTH: f32 = 200.0;
+const MINIMAP_HEIGHT: f32 = 300.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct View {
+    pub x: f32,
+    pub y: f32,
+    pub zoom: f32,
+}
+
+impl Default for View {
+    fn default() -> Self {
+        View {x: 0.0, y: 0.0, zoom: 1.0}
+    }
+}
+
+impl View {
+    // The whole width x height graph, centered in the window.
+    pub fn fit(width: f32, height: f32) -> View {
+        let zoom = (VIEWPORT_WIDTH / width).min(VIEWPORT_HEIGHT / height).clamp(MIN_ZOOM, MAX_ZOOM);
+        let (visiblew, visibleh) = (VIEWPORT_WIDTH / zoom, VIEWPORT_HEIGHT / zoom);
+        View {x: (width - visiblew) / 2.0, y: (height - visibleh) / 2.0, zoom}
+    }
+
+    pub fn view_box(&self) -> String {
+        format!("{} {} {} {}", self.x, self.y, VIEWPORT_WIDTH / self.zoom, VIEWPORT_HEIGHT / self.zoom)
+    }
+
+    // Zoom by factor, keeping the graph point under the window pixel (px, py) where it is.
+    pub fn zoom_at(&self, factor: f32, px: f32, py: f32) -> View {
+        let zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        let (gx, gy) = (self.x + px / self.zoom, self.y + py / self.zoom);
+        View {x: gx - px / zoom, y: gy - py / zoom, zoom}
+    }
+
+    // Move by (dx, dy) window pixels, the way dragging does.
+    pub fn pan(&self, dx: f32, dy: f32) -> View {
+        View {x: self.x - dx / self.zoom, y: self.y - dy / self.zoom, zoom: self.zoom}
+    }
+
+    pub fn center_on(&self, x: f32, y: f32) -> View {
+        View {x: x - VIEWPORT_WIDTH / self.zoom / 2.0, y: y - VIEWPORT_HEIGHT / self.zoom / 2.0, zoom: self.zoom}
+    }
+
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.x && x <= self.x + VIEWPORT_WIDTH / self.zoom && y >= self.y && y <= self.y + VIEWPORT_HEIGHT / self.zoom
+    }
+}
+
+// The whole graph in miniature (points are (x, y, color) in graph coordinates),
+// with the part in the window outlined. Clicking it centers the window there.
+pub fn render_minimap(width: f32, height: f32, points: &[(f32, f32, String)], view: &View, oncenter: &Callback<(f32, f32)>) -> Html {
+    let scale = (MINIMAP_WIDTH / width).min(MINIMAP_HEIGHT / height);
+    let onclick = {
+        let oncenter = oncenter.clone();
+        move |event: MouseEvent| {
+            let Some(minimap) = event.current_target().and_then(|target| target.dyn_into::<Element>().ok()) else { return };
+            let rect = minimap.get_bounding_client_rect();
+            let (px, py) = (event.client_x() as f32 - rect.left() as f32, event.client_y() as f32 - rect.top() as f32);
+            oncenter.emit((px / scale, py / scale));
+        }
+    };
+    let dots: Vec<Html> = points.iter().map(|(x, y, color)| html! {
+        <circle cx={format!("{}", x * scale)} cy={format!("{}", y * scale)} r="1.5" fill={color.clone()} pointer-events="none" />
+    }).collect();
+    html! {
+        <svg width={format!("{}", (width * scale) as i32)} height={format!("{}", (height * scale) as i32)} style="border: 1px solid black; cursor: pointer; background: white;" onclick={onclick}>
+            { for dots.into_iter() }
+            <rect x={format!("{}", view.x * scale)} y={format!("{}", view.y * scale)} width={format!("{}", VIEWPORT_WIDTH / view.zoom * scale)} height={format!("{}", VIEWPORT_HEIGHT / view.zoom * scale)} fill="none" stroke="red" stroke-width="1.5" pointer-events="none" />
+        </svg>
+    }
+}